@@ -0,0 +1,346 @@
+use std::path::Path;
+
+use super::issue;
+use super::keyword;
+
+use issue::Blame;
+use keyword::Keyword;
+
+pub const DEFAULT_SNIPPET_LINES: usize = 3;
+const PLACEHOLDERS: &[&str] = &[
+    "keyword",
+    "title",
+    "details",
+    "file",
+    "line",
+    "author",
+    "commit",
+    "date",
+    "permalink",
+    "snippet",
+    "branch",
+];
+
+// Languages for the fences of code snippets, by file extension. Any other
+// extension is used as the language as is, which GitHub and GitLab
+// understand for most languages.
+const FENCE_LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("jsx", "jsx"),
+    ("ts", "typescript"),
+    ("tsx", "tsx"),
+    ("rb", "ruby"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("hs", "haskell"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("erl", "erlang"),
+    ("pl", "perl"),
+    ("ps1", "powershell"),
+    ("vb", "vbnet"),
+    ("tex", "latex"),
+    ("md", "markdown"),
+    ("yml", "yaml"),
+    ("htm", "html"),
+];
+
+pub struct BodyBuilder<'a> {
+    keyword: &'a Keyword,
+    title: &'a str,
+    file: &'a str,
+    line: u32,
+    details: String,
+    branch: &'a str,
+    blame: Option<&'a Blame>,
+    permalink: Option<String>,
+    snippet: Option<String>,
+}
+
+impl<'a> BodyBuilder<'a> {
+    pub fn new(
+        keyword: &'a Keyword,
+        title: &'a str,
+        file: &'a str,
+        line: u32,
+    ) -> BodyBuilder<'a> {
+        //! Starts the description of a new issue for a "todo" comment on a
+        //! line of a file.
+        BodyBuilder {
+            keyword,
+            title,
+            file,
+            line,
+            details: String::new(),
+            branch: "",
+            blame: None,
+            permalink: None,
+            snippet: None,
+        }
+    }
+
+    pub fn details(mut self, details: &[String]) -> BodyBuilder<'a> {
+        //! Adds the comment lines continuing the todo.
+        self.details = details.join("\n");
+        self
+    }
+
+    pub fn branch(mut self, branch: &'a str) -> BodyBuilder<'a> {
+        //! Adds the branch the todo was found on.
+        self.branch = branch;
+        self
+    }
+
+    pub fn blame(mut self, blame: Option<&'a Blame>) -> BodyBuilder<'a> {
+        //! Adds the author and commit that added the todo.
+        self.blame = blame;
+        self
+    }
+
+    pub fn permalink(mut self, blob_url: Option<&str>) -> BodyBuilder<'a> {
+        //! Adds a link to the todo's line, given the web url of the files
+        //! in a commit, e.g. https://github.com/owner/repo/blob/{sha}.
+        self.permalink = blob_url.map(|url| {
+            format!("{}/{}#L{}", url, encode_path(self.file), self.line)
+        });
+        self
+    }
+
+    pub fn snippet(
+        mut self,
+        lines: &[String],
+        end: u32,
+        context: usize,
+    ) -> BodyBuilder<'a> {
+        //! Adds a fenced code block of the todo's lines up to the end line,
+        //! with the number of context lines before and after them.
+        let start = (self.line as usize - 1).saturating_sub(context);
+        let end = (end as usize + context).min(lines.len());
+        let code = lines[start..end].join("\n");
+
+        // The fence must be longer than any run of backticks in the code.
+        let longest = code
+            .split(|c| c != '`')
+            .map(|run| run.len())
+            .max()
+            .unwrap_or(0);
+        let fence = "`".repeat(longest.max(2) + 1);
+        self.snippet = Some(format!(
+            "{}{}\n{}\n{}",
+            fence,
+            get_language(self.file),
+            code,
+            fence
+        ));
+        self
+    }
+
+    pub fn build_title(&self) -> String {
+        //! Creates the title, using the keyword's title template if it has
+        //! one and starting with its title prefix. Line breaks in templates
+        //! become spaces, since titles are a single line.
+        let values = self.get_values();
+        let mut title = match self.keyword.get_title_template() {
+            Some(template) => render(template, &values),
+            None => self.title.to_string(),
+        };
+        if let Some(prefix) = self.keyword.get_title_prefix() {
+            title = format!("{} {}", render(prefix, &values).trim(), title);
+        }
+
+        let lines: Vec<&str> = title.lines().map(|line| line.trim()).collect();
+        lines.join(" ").trim().to_string()
+    }
+
+    pub fn build(self) -> String {
+        //! Creates the description, using the keyword's body template if it
+        //! has one.
+        let blame = self.blame;
+        if let Some(template) = self.keyword.get_template() {
+            return render(template, &self.get_values());
+        }
+
+        let mut body = self.details.clone();
+        if !body.is_empty() {
+            body += "\n\n";
+        }
+        body += &format!(
+            "Found a {} comment on line {} of file {}",
+            self.keyword.get_name(),
+            self.line,
+            self.file
+        );
+        if let Some(blame) = blame {
+            body += &format!(
+                "\nAdded by {} in {} on {}",
+                blame.author,
+                blame.get_short_commit(),
+                blame.get_date()
+            );
+        }
+        if let Some(permalink) = &self.permalink {
+            body += &format!("\n\n{}", permalink);
+        }
+        if let Some(snippet) = &self.snippet {
+            body += &format!("\n\n{}", snippet);
+        }
+
+        body
+    }
+}
+
+impl BodyBuilder<'_> {
+    fn get_values(&self) -> Vec<(&'static str, String)> {
+        //! Returns the value of every placeholder. The blame and permalink
+        //! placeholders are empty for uncommitted todos.
+        let blame = self.blame;
+
+        vec![
+            ("keyword", self.keyword.get_name().to_string()),
+            ("title", self.title.to_string()),
+            ("details", self.details.clone()),
+            ("file", self.file.to_string()),
+            ("line", self.line.to_string()),
+            ("author", blame.map_or(String::new(), |b| b.author.clone())),
+            (
+                "commit",
+                blame.map_or(String::new(), |b| b.get_short_commit().into()),
+            ),
+            ("date", blame.map_or(String::new(), |b| b.get_date())),
+            ("permalink", self.permalink.clone().unwrap_or_default()),
+            ("snippet", self.snippet.clone().unwrap_or_default()),
+            ("branch", self.branch.to_string()),
+        ]
+    }
+}
+
+pub fn check_template(template: &str) -> Result<(), String> {
+    //! Checks that every placeholder in a template is known and closed.
+    //!
+    //! Returns an error message naming the first one that isn't.
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => {
+                return Err(format!(
+                    "\"{}\" is missing its closing }}}}.",
+                    rest[start..].lines().next().unwrap_or("")
+                ))
+            }
+        };
+
+        let name = after[..end].trim();
+        if !PLACEHOLDERS.contains(&name) {
+            let expected: Vec<String> = PLACEHOLDERS
+                .iter()
+                .map(|p| format!("{{{{{}}}}}", p))
+                .collect();
+            return Err(format!(
+                "Unknown placeholder \"{{{{{}}}}}\". Expected one of: {}.",
+                name,
+                expected.join(", ")
+            ));
+        }
+        rest = &after[end + 2..];
+    }
+
+    Ok(())
+}
+
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    //! Replaces every placeholder of the form `{{name}}` in a template
+    //! with its value. Values are inserted as is, so placeholders in them
+    //! aren't replaced.
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+
+        rendered += &rest[..start];
+        let name = after[..end].trim();
+        match values.iter().find(|(placeholder, _)| *placeholder == name) {
+            Some((_, value)) => rendered += value,
+            None => rendered += &rest[start..start + end + 4],
+        }
+        rest = &after[end + 2..];
+    }
+    rendered += rest;
+
+    rendered
+}
+
+fn get_language(path: &str) -> String {
+    //! Returns the language of a code fence for the file's extension.
+    let extension = match Path::new(path).extension() {
+        Some(extension) => extension.to_string_lossy().to_lowercase(),
+        None => return String::new(),
+    };
+
+    FENCE_LANGUAGES
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map_or(extension.clone(), |(_, language)| language.to_string())
+}
+
+fn encode_path(path: &str) -> String {
+    //! Percent-encodes the characters of a file path that would break a url.
+    path.chars()
+        .map(|c| match c {
+            '%' => "%25".to_string(),
+            ' ' => "%20".to_string(),
+            '#' => "%23".to_string(),
+            '?' => "%3F".to_string(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_replaces_known_placeholders() {
+        let values = [
+            ("title", "Fix {{file}}".to_string()),
+            ("file", "src/a.rs".to_string()),
+        ];
+
+        assert_eq!(
+            render("{{title}} in {{ file }}:{{line}}", &values),
+            "Fix {{file}} in src/a.rs:{{line}}"
+        );
+        assert_eq!(render("{{title", &values), "{{title");
+        assert_eq!(render("no placeholders", &values), "no placeholders");
+    }
+
+    #[test]
+    fn check_template_rejects_unknown_and_unclosed_placeholders() {
+        assert_eq!(check_template("{{keyword}}: {{ title }}"), Ok(()));
+        assert_eq!(
+            check_template("{{title}}\n{{file"),
+            Err("\"{{file\" is missing its closing }}.".to_string())
+        );
+
+        let error = check_template("{{owner}}").unwrap_err();
+        assert!(error.starts_with("Unknown placeholder \"{{owner}}\"."));
+        assert!(error.ends_with("{{snippet}}, {{branch}}."));
+    }
+}
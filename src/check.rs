@@ -0,0 +1,367 @@
+use std::fs;
+use std::path::Path;
+
+use console::style;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::cli;
+use super::command;
+use super::fingerprint;
+use super::issue;
+use super::parse;
+use super::report;
+
+use cli::{Args, Format};
+use fingerprint::Fingerprint;
+use issue::{Issue, Response};
+
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(default)]
+    title: String,
+    fingerprint: String,
+}
+
+pub fn run(args: &Args) -> bool {
+    //! Searches for todos and compares them against the baseline file
+    //! and the todos at the base ref, if given. Every todo missing from
+    //! both that doesn't reference an issue is new, and is output as
+    //! `file:line:column: KEYWORD: title` like compiler errors are.
+    //! Without a baseline or base ref, only the maximum is checked.
+    //!
+    //! Writes the todos found to the baseline file instead when running
+    //! with --update-baseline.
+    //!
+    //! Returns a bool indicating whether the check passed, i.e. there are
+    //! no new todos and no more todos than the configured maximum.
+    let config = args.get_config();
+    let has_baseline =
+        config.get_baseline().is_some() || args.get_base_ref().is_some();
+    if !has_baseline && config.get_max().is_none() && !args.is_update_baseline()
+    {
+        cli::print_error(
+            "Nothing to check against. Set a baseline with --baseline or \
+             --base-ref, or a maximum with --max.",
+        );
+        return false;
+    }
+
+    let files = command::get_tracked_files();
+    let mut found = match parse::find_all_todos(&files, &[], None, args) {
+        Some(found) => found,
+        None => return false,
+    };
+
+    if args.is_update_baseline() {
+        return match config.get_baseline() {
+            Some(path) => write_baseline(path, &found),
+            None => {
+                cli::print_error(
+                    "No baseline file to update. Set one with --baseline.",
+                );
+                false
+            }
+        };
+    }
+
+    let mut baseline = Vec::new();
+    if let Some(path) = config.get_baseline() {
+        match read_baseline(path) {
+            Some(entries) => baseline.extend(entries),
+            None => return false,
+        }
+    }
+    if let Some(reference) = args.get_base_ref() {
+        match parse::find_all_todos_at(reference, args) {
+            Some(old) => baseline.extend(
+                old.iter()
+                    .flat_map(|(_, issues)| issues.iter())
+                    .map(|issue| (issue.get_title(), *issue.get_fingerprint())),
+            ),
+            None => return false,
+        }
+    }
+
+    let total: usize = found.iter().map(|(_, issues)| issues.len()).sum();
+    let baseline = match has_baseline {
+        true => Some(baseline),
+        false => None,
+    };
+    retain_new(&mut found, baseline);
+
+    if args.get_format() != Format::Text {
+        report::print_todos(&found, args.get_keywords(), args.get_format());
+    }
+    for issue in found.iter().flat_map(|(_, issues)| issues.iter()) {
+        let location = issue.get_location();
+        cli::print_info(&format!(
+            "{}:{}:{}: {}: {}",
+            style(&location.file).bold(),
+            location.line,
+            location.column,
+            style(issue.get_keyword()).yellow(),
+            issue.get_title()
+        ));
+    }
+
+    let new: usize = found.iter().map(|(_, issues)| issues.len()).sum();
+
+    check_counts(new, total, config.get_max(), has_baseline)
+}
+
+fn retain_new(
+    found: &mut Vec<(String, Vec<Issue>)>,
+    baseline: Option<Vec<(String, Fingerprint)>>,
+) {
+    //! Keeps only the todos that are new, i.e. match no todo of the
+    //! baseline and don't reference an issue. Without a baseline, no todo
+    //! is new.
+    let baseline = match baseline {
+        Some(baseline) => baseline,
+        None => {
+            found.clear();
+            return;
+        }
+    };
+
+    // Baseline todos are matched like remote issues, so todos that were
+    // edited or moved since aren't reported as new.
+    let existing: Vec<Response> = baseline
+        .into_iter()
+        .enumerate()
+        .map(|(index, (title, fingerprint))| {
+            Response::new(title, index + 1, Some(fingerprint.to_marker()), true)
+        })
+        .collect();
+    let mut untracked: Vec<&mut Issue> = found
+        .iter_mut()
+        .flat_map(|(_, issues)| issues.iter_mut())
+        .filter(|issue| issue.get_existing().is_none())
+        .collect();
+    fingerprint::match_existing(&mut untracked, &existing);
+
+    for (_, issues) in found.iter_mut() {
+        issues.retain(|issue| issue.get_existing().is_none());
+    }
+    found.retain(|(_, issues)| !issues.is_empty());
+}
+
+fn check_counts(
+    new: usize,
+    total: usize,
+    max: Option<usize>,
+    has_baseline: bool,
+) -> bool {
+    //! Outputs whether the check passed, i.e. there are no new todos and
+    //! the total doesn't exceed the maximum, if any.
+    //!
+    //! Returns a bool indicating whether the check passed.
+    let mut is_successful = true;
+    if new > 0 {
+        cli::print_error(&format!(
+            "Found {} new {} without an issue.",
+            new,
+            cli::handle_plural(&new, "comment")
+        ));
+        is_successful = false;
+    }
+    if let Some(max) = max {
+        if total > max {
+            cli::print_error(&format!(
+                "Found {} {}, more than the maximum of {}.",
+                total,
+                cli::handle_plural(&total, "comment"),
+                max
+            ));
+            is_successful = false;
+        }
+    }
+    if is_successful && has_baseline {
+        cli::print_success(&format!(
+            "No new comments found among {} total.",
+            total
+        ));
+    } else if is_successful {
+        cli::print_success(&format!(
+            "Found {} {}, within the maximum.",
+            total,
+            cli::handle_plural(&total, "comment")
+        ));
+    }
+
+    is_successful
+}
+
+fn read_baseline(path: &str) -> Option<Vec<(String, Fingerprint)>> {
+    //! Reads the fingerprints of the todos in a baseline file, which is a
+    //! JSON array of objects with a `fingerprint` field. Both the files
+    //! written with --update-baseline and the output of --format json work.
+    //!
+    //! Returns None and outputs an error if the file can't be read.
+    let full_path = Path::new(&command::get_repo_root()).join(path);
+    let contents = match fs::read_to_string(&full_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            cli::print_error(&format!(
+                "Failed to read baseline {}: {}. \
+                 Create it with --update-baseline.",
+                path, e
+            ));
+            return None;
+        }
+    };
+    let entries: Vec<Entry> = match serde_json::from_str(&contents) {
+        Ok(entries) => entries,
+        Err(e) => {
+            cli::print_error(&format!("Invalid baseline {}: {}", path, e));
+            return None;
+        }
+    };
+
+    Some(
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                Some((entry.title, Fingerprint::parse(&entry.fingerprint)?))
+            })
+            .collect(),
+    )
+}
+
+fn write_baseline(path: &str, found: &[(String, Vec<Issue>)]) -> bool {
+    //! Writes every todo found to the baseline file. Line numbers are left
+    //! out so the file only changes when todos are added or removed.
+    //!
+    //! Returns a bool indicating whether the file was written.
+    let mut todos: Vec<&Issue> =
+        found.iter().flat_map(|(_, issues)| issues.iter()).collect();
+    todos.sort_by_key(|todo| {
+        (
+            todo.get_location().file.clone(),
+            todo.get_fingerprint().to_string(),
+        )
+    });
+    let entries: Vec<_> = todos
+        .iter()
+        .map(|todo| {
+            json!({
+                "file": &todo.get_location().file,
+                "keyword": todo.get_keyword(),
+                "title": todo.get_title(),
+                "fingerprint": todo.get_fingerprint().to_string(),
+            })
+        })
+        .collect();
+
+    let full_path = Path::new(&command::get_repo_root()).join(path);
+    let contents = serde_json::to_string_pretty(&entries).unwrap_or_default();
+    match fs::write(&full_path, contents + "\n") {
+        Ok(_) => {
+            cli::print_success(&format!(
+                "Wrote {} {} to {}.",
+                entries.len(),
+                cli::handle_plural(&entries.len(), "comment"),
+                path
+            ));
+            true
+        }
+        Err(e) => {
+            cli::print_error(&format!(
+                "Failed to write baseline {}: {}",
+                path, e
+            ));
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issue::{Annotations, Location};
+    use crate::keyword::Keyword;
+    use cli::Mode;
+
+    fn todo(title: &str, path: &str, context: &[&str]) -> Issue {
+        let location = Location {
+            file: path.to_string(),
+            line: 1,
+            column: 0,
+        };
+        Issue::new(
+            title.to_string(),
+            String::new(),
+            &Keyword::new("TODO"),
+            location,
+            Annotations::default(),
+            Fingerprint::new("TODO", title, path, context),
+        )
+    }
+
+    fn titles(found: &[(String, Vec<Issue>)]) -> Vec<String> {
+        found
+            .iter()
+            .flat_map(|(_, issues)| issues.iter())
+            .map(|issue| issue.get_title())
+            .collect()
+    }
+
+    #[test]
+    fn retain_new_matches_moved_and_edited_todos() {
+        let old = todo("Fix it", "a.rs", &["x"]);
+        let edited = todo("Old text", "b.rs", &["y"]);
+        let baseline = vec![
+            (old.get_title(), *old.get_fingerprint()),
+            (edited.get_title(), *edited.get_fingerprint()),
+        ];
+        let mut found = vec![
+            ("c.rs".to_string(), vec![todo("Fix it", "c.rs", &["z"])]),
+            (
+                "b.rs".to_string(),
+                vec![
+                    todo("New text", "b.rs", &["y"]),
+                    todo("New", "b.rs", &[]),
+                ],
+            ),
+        ];
+
+        retain_new(&mut found, Some(baseline));
+        assert_eq!(titles(&found), ["New"]);
+        assert_eq!(found.len(), 1);
+    }
+
+    #[test]
+    fn retain_new_skips_todos_referencing_issues() {
+        let mut referenced = todo("Tracked", "a.rs", &[]);
+        referenced.set_existing(42);
+        let mut found = vec![("a.rs".to_string(), vec![referenced])];
+
+        retain_new(&mut found, Some(Vec::new()));
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn retain_new_finds_nothing_new_without_a_baseline() {
+        let mut found =
+            vec![("a.rs".to_string(), vec![todo("New", "a.rs", &[])])];
+
+        retain_new(&mut found, None);
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn check_counts_applies_the_maximum_to_the_total() {
+        assert!(check_counts(0, 4, Some(4), true));
+        assert!(!check_counts(0, 5, Some(4), true));
+        assert!(!check_counts(0, 5, Some(4), false));
+        assert!(!check_counts(1, 1, None, true));
+        assert!(!check_counts(1, 1, Some(10), true));
+        assert!(check_counts(0, 3, None, true));
+    }
+
+    #[test]
+    fn run_needs_something_to_check_against() {
+        assert!(!run(&Args::new(Mode::Check, &["TODO"])));
+    }
+}
@@ -1,38 +1,90 @@
 use std::collections::HashMap;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Editor, Select};
 use glob::Pattern;
 
 use super::command;
+use super::config;
 use super::issue;
-use super::request;
+use super::keyword;
+use super::tracker;
 
+use config::{Config, KeywordConfig};
 use issue::Issue;
-use request::Request;
+use keyword::Keyword;
+use tracker::IssueTracker;
 
 const SELECTIONS: &[&str] = &["Open Issue", "Edit Issue", "Skip Issue", "Exit"];
-const ALL_FILES: &str = "*";
 
 const OPEN: usize = 0;
 const EDIT: usize = 1;
 const SKIP: usize = 2;
 
+const DEFAULT_MAX_ISSUES: usize = 25;
+const FORMATS: &[&str] = &["text", "json", "jsonl", "sarif"];
+const SORTS: &[&str] = &["file", "age"];
+
+// Set when stdout is reserved for machine readable output, in which case
+// everything meant for humans is printed to stderr instead.
+static IS_STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+pub enum Mode {
+    Run,
+    Sync,
+    Check,
+    ShowConfig,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    JsonLines,
+    Sarif,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sort {
+    File,
+    Age,
+}
+
 pub struct Args {
-    pattern: Pattern,
-    token: String,
+    mode: Mode,
+    config: Config,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    keywords: Vec<Keyword>,
+    token: Option<String>,
     is_dry_run: bool,
     is_verbose: bool,
+    is_batch: bool,
+    max_issues: usize,
+    format: Format,
+    per_page: usize,
+    base_ref: Option<String>,
+    is_update_baseline: bool,
+    since: Option<String>,
+    older_than: Option<u64>,
+    sort: Sort,
+    path_labels: Vec<(Pattern, String)>,
 }
 
 impl Args {
-    pub fn get_token(&self) -> String {
-        self.token.clone()
+    pub fn get_mode(&self) -> &Mode {
+        &self.mode
     }
 
-    pub fn get_pattern(&self) -> &Pattern {
-        &self.pattern
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn get_token(&self) -> Option<&str> {
+        self.token.as_deref()
     }
 
     pub fn is_dry_run(&self) -> bool {
@@ -42,29 +94,243 @@ impl Args {
     pub fn is_verbose(&self) -> bool {
         self.is_verbose
     }
+
+    pub fn is_batch(&self) -> bool {
+        self.is_batch
+    }
+
+    pub fn get_max_issues(&self) -> usize {
+        self.max_issues
+    }
+
+    pub fn get_format(&self) -> Format {
+        self.format
+    }
+
+    pub fn get_per_page(&self) -> usize {
+        self.per_page
+    }
+
+    pub fn get_base_ref(&self) -> Option<&str> {
+        self.base_ref.as_deref()
+    }
+
+    pub fn is_update_baseline(&self) -> bool {
+        self.is_update_baseline
+    }
+
+    pub fn get_since(&self) -> Option<&str> {
+        self.since.as_deref()
+    }
+
+    pub fn get_older_than(&self) -> Option<u64> {
+        self.older_than
+    }
+
+    pub fn get_sort(&self) -> Sort {
+        self.sort
+    }
+
+    pub fn get_path_labels(&self, path: &str) -> Vec<&str> {
+        //! Returns the labels of every path pattern the file matches.
+        self.path_labels
+            .iter()
+            .filter(|(pattern, _)| pattern.matches(path))
+            .map(|(_, label)| label.as_str())
+            .collect()
+    }
+
+    pub fn get_keywords(&self) -> &[Keyword] {
+        &self.keywords
+    }
+
+    pub fn is_included(&self, path: &str) -> bool {
+        //! Returns whether a file matches any include pattern (or there are
+        //! none) and doesn't match any exclude pattern.
+        (self.include.is_empty()
+            || self.include.iter().any(|pattern| pattern.matches(path)))
+            && !self.exclude.iter().any(|pattern| pattern.matches(path))
+    }
+}
+
+#[cfg(test)]
+impl Args {
+    pub fn new(mode: Mode, keywords: &[&str]) -> Args {
+        //! Creates arguments with the defaults of the command line, for
+        //! testing code that takes them.
+        Args {
+            mode,
+            config: Config::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            keywords: keywords.iter().map(|name| Keyword::new(name)).collect(),
+            token: None,
+            is_dry_run: false,
+            is_verbose: false,
+            is_batch: false,
+            max_issues: 0,
+            format: Format::Text,
+            per_page: 100,
+            base_ref: None,
+            is_update_baseline: false,
+            since: None,
+            older_than: None,
+            sort: Sort::File,
+            path_labels: Vec::new(),
+        }
+    }
 }
 
 pub fn init() -> Option<Args> {
-    //! Initializes the CLI and parses command line arguments.
+    //! Initializes the CLI and parses command line arguments, merged on top
+    //! of the settings in the repository's config file.
     //!
     //! Returns an Option containing the Args as a struct or None
-    //! if the user is not in a git repo.
+    //! if the user is not in a git repo or the config is invalid.
     let matches = App::new("todo-issue")
         .version("0.1.1")
         .author("Warren Galyen <wgalyen@hotmail.com>")
         .about("Converts TODO comments into GitHub issues")
+        .subcommand(
+            SubCommand::with_name("sync")
+                .about(
+                    "Closes open issues whose comment was removed from the code",
+                )
+                .arg(
+                    Arg::with_name("token")
+                        .help("Sets the token for user")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .short("n")
+                        .long("dry-run")
+                        .help("Lists the issues to close without closing them"),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .help("Makes output more descriptive"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about(
+                    "Fails if comments were added since a baseline, \
+                     for use in CI",
+                )
+                .arg(
+                    Arg::with_name("baseline")
+                        .long("baseline")
+                        .value_name("FILE")
+                        .help("Compares against the comments listed in a file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("base-ref")
+                        .long("base-ref")
+                        .value_name("REF")
+                        .help(
+                            "Compares against the comments in a git ref, \
+                             e.g. origin/main",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("max")
+                        .long("max")
+                        .value_name("COUNT")
+                        .help("Fails if more comments than this are found")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("update-baseline")
+                        .long("update-baseline")
+                        .help(
+                            "Writes every comment found to the baseline file \
+                             instead of checking",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .help("Makes output more descriptive"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("config").about(
+            "Prints the effective config after merging the config file \
+             and command line arguments",
+        ))
         .arg(
             Arg::with_name("token")
                 .help("Sets the token for user")
                 .index(1),
         )
+        .arg(
+            Arg::with_name("token-option")
+                .long("token")
+                .value_name("TOKEN")
+                .help(
+                    "Sets the access token, instead of looking it up in the \
+                     environment, the GitHub CLI or git credentials",
+                )
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .visible_alias("create-all")
+                .help(
+                    "Opens every new issue (or closes every issue when \
+                     syncing) without prompting",
+                )
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("max-issues")
+                .long("max-issues")
+                .value_name("COUNT")
+                .help(
+                    "Sets the most issues --yes may open or close in one run \
+                     (defaults to 25)",
+                )
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help(
+                    "Prints every comment found as json, jsonl or sarif to \
+                     stdout, with all other output on stderr",
+                )
+                .possible_values(FORMATS)
+                .takes_value(true)
+                .global(true),
+        )
         .arg(
             Arg::with_name("pattern")
                 .short("p")
                 .long("pattern")
                 .value_name("PATTERN")
                 .help("Sets a glob pattern to narrow search for TODO comments")
-                .takes_value(true),
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("PATTERN")
+                .help("Sets a glob pattern for files to skip (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
         )
         .arg(
             Arg::with_name("dry-run")
@@ -78,6 +344,113 @@ pub fn init() -> Option<Args> {
                 .long("verbose")
                 .help("Makes output more descriptive"),
         )
+        .arg(
+            Arg::with_name("since")
+                .long("since")
+                .value_name("REF")
+                .help(
+                    "Only searches lines added since the current branch \
+                     forked from a git ref, e.g. origin/main",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("older-than")
+                .long("older-than")
+                .value_name("DAYS")
+                .help("Only includes comments committed at least DAYS ago")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .help("Orders new issues by file or by age, oldest first")
+                .possible_values(SORTS)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("keyword")
+                .short("k")
+                .long("keyword")
+                .value_name("KEYWORD[=LABEL]")
+                .help(
+                    "Searches for comments with this keyword instead of TODO, \
+                     optionally mapped to a label (repeatable)",
+                )
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("label")
+                .long("label")
+                .value_name("LABEL")
+                .help("Adds a label to every issue opened (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("assignee")
+                .long("assignee")
+                .value_name("USER")
+                .help("Assigns every issue opened to a user (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("milestone")
+                .long("milestone")
+                .value_name("TITLE")
+                .help("Adds every issue opened to the milestone with a title")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .value_name("KEYWORD=TEMPLATE")
+                .help("Sets the issue body template for a keyword (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("remote")
+                .long("remote")
+                .value_name("NAME")
+                .help(
+                    "Sets the git remote to open issues in, instead of the one \
+                     pointing to GitHub",
+                )
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("api-url")
+                .long("api-url")
+                .value_name("URL")
+                .help(
+                    "Sets the GitHub API endpoint, e.g. \
+                     https://github.example.com/api/v3",
+                )
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("per-page")
+                .long("per-page")
+                .value_name("COUNT")
+                .help("Sets how many issues to fetch per API request (max 100)")
+                .takes_value(true)
+                .global(true),
+        )
         .get_matches();
 
     if !command::is_git_repo() {
@@ -85,36 +458,266 @@ pub fn init() -> Option<Args> {
         return None;
     }
 
+    let (mode, matches) = match matches.subcommand() {
+        ("sync", Some(sub_matches)) => (Mode::Sync, sub_matches),
+        ("check", Some(sub_matches)) => (Mode::Check, sub_matches),
+        ("config", Some(sub_matches)) => (Mode::ShowConfig, sub_matches),
+        _ => (Mode::Run, &matches),
+    };
+    // Syncing closes the issues of every comment it doesn't find, so it
+    // must search every file the issues could have been opened for.
+    if matches!(mode, Mode::Sync)
+        && (matches.is_present("pattern") || matches.is_present("exclude"))
+    {
+        print_error(
+            "--pattern and --exclude can't be used with sync, since the \
+             issues of comments in the files skipped would be closed.",
+        );
+        return None;
+    }
+
+    let mut config = match Config::load(&command::get_repo_root()) {
+        Ok(config) => config,
+        Err(e) => {
+            print_error(&e);
+            return None;
+        }
+    };
+    merge_args(&mut config, matches)?;
+
+    let include = parse_patterns(&config.include)?;
+    let mut exclude = parse_patterns(&config.exclude)?;
+    // Issues in the local store quote their comments, so skip them.
+    if config.get_tracker() == Some(tracker::LOCAL) {
+        let dir = Pattern::escape(config.get_local_dir().trim_matches('/'));
+        exclude.extend(parse_patterns(&[format!("{}/**", dir)])?);
+    }
+    // The baseline lists the titles of comments, which aren't comments.
+    if let Some(baseline) = config.get_baseline() {
+        exclude.push(Pattern::new(&Pattern::escape(baseline)).unwrap());
+    }
+    let keywords = config.get_keywords();
+    let patterns: Vec<String> = config.path_labels.keys().cloned().collect();
+    let path_labels = parse_patterns(&patterns)?
+        .into_iter()
+        .zip(config.path_labels.values().cloned())
+        .collect();
+    for keyword in &keywords {
+        if let Err(e) = keyword.check_templates() {
+            print_error(&e);
+            return None;
+        }
+    }
+
+    if let Some(name) = config.get_tracker() {
+        if !tracker::TRACKERS.contains(&name) {
+            print_error(&format!(
+                "Unsupported tracker \"{}\". Expected one of: {}.",
+                name,
+                tracker::TRACKERS.join(", ")
+            ));
+            return None;
+        }
+    }
+
     let is_dry_run = matches.is_present("dry-run");
     let is_verbose = matches.is_present("verbose");
 
-    let pattern_value = matches.value_of("pattern").unwrap_or(ALL_FILES);
+    let per_page = match matches.value_of("per-page") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(n) if n > 0 && n <= tracker::MAX_PER_PAGE => n,
+            _ => {
+                print_error(&format!(
+                    "--per-page must be a number between 1 and {}.",
+                    tracker::MAX_PER_PAGE
+                ));
+                return None;
+            }
+        },
+        None => tracker::DEFAULT_PER_PAGE,
+    };
 
-    let pattern = match Pattern::new(pattern_value) {
-        Ok(pattern) => pattern,
-        Err(_) => Pattern::new(ALL_FILES).unwrap(),
+    let format = match matches.value_of("format") {
+        Some("json") => Format::Json,
+        Some("jsonl") => Format::JsonLines,
+        Some("sarif") => Format::Sarif,
+        _ => Format::Text,
+    };
+    IS_STDOUT_RESERVED.store(format != Format::Text, Ordering::Relaxed);
+
+    let is_batch = matches.is_present("yes");
+    let max_issues = match matches.value_of("max-issues") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                print_error("--max-issues must be a positive number.");
+                return None;
+            }
+        },
+        None => DEFAULT_MAX_ISSUES,
     };
 
-    let token = match matches.value_of("token") {
-        Some(t) => t.to_string(),
-        None => match is_dry_run {
-            true => String::new(),
-            false => command::read_access_token(),
+    let token = matches
+        .value_of("token-option")
+        .or_else(|| matches.value_of("token"))
+        .map(|token| token.to_string());
+    let base_ref = matches.value_of("base-ref").map(|r| r.to_string());
+    let is_update_baseline = matches.is_present("update-baseline");
+    let since = matches.value_of("since").map(|r| r.to_string());
+    let older_than = match matches.value_of("older-than") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(days) => Some(days),
+            Err(_) => {
+                print_error("--older-than must be a number of days.");
+                return None;
+            }
         },
+        None => None,
+    };
+    let sort = match matches.value_of("sort") {
+        Some("age") => Sort::Age,
+        _ => Sort::File,
     };
 
-    return Some(Args {
-        pattern,
+    Some(Args {
+        mode,
+        config,
+        include,
+        exclude,
+        keywords,
         token,
         is_dry_run,
         is_verbose,
-    });
+        is_batch,
+        max_issues,
+        format,
+        per_page,
+        base_ref,
+        is_update_baseline,
+        since,
+        older_than,
+        sort,
+        path_labels,
+    })
+}
+
+fn merge_args(config: &mut Config, matches: &ArgMatches) -> Option<()> {
+    //! Overrides the config file's settings with any command line
+    //! arguments given.
+    //!
+    //! Returns None and outputs an error if an argument is malformed.
+    if let Some(remote) = matches.value_of("remote") {
+        config.remote = Some(remote.to_string());
+    }
+    if let Some(api_url) = matches.value_of("api-url") {
+        config.api_url = Some(api_url.to_string());
+    }
+    if let Some(pattern) = matches.value_of("pattern") {
+        config.include = vec![pattern.to_string()];
+    }
+    if let Some(patterns) = matches.values_of("exclude") {
+        config.exclude = patterns.map(|p| p.to_string()).collect();
+    }
+    for label in matches.values_of("label").into_iter().flatten() {
+        if !config.labels.iter().any(|l| l == label) {
+            config.labels.push(label.to_string());
+        }
+    }
+    for assignee in matches.values_of("assignee").into_iter().flatten() {
+        if !config.assignees.iter().any(|a| a == assignee) {
+            config.assignees.push(assignee.to_string());
+        }
+    }
+    if let Some(milestone) = matches.value_of("milestone") {
+        config.milestone = Some(milestone.to_string());
+    }
+    if let Some(baseline) = matches.value_of("baseline") {
+        config.check.get_or_insert_with(Default::default).baseline =
+            Some(baseline.to_string());
+    }
+    if let Some(value) = matches.value_of("max") {
+        match value.parse::<usize>() {
+            Ok(max) => {
+                config.check.get_or_insert_with(Default::default).max =
+                    Some(max)
+            }
+            Err(_) => {
+                print_error("--max must be a number.");
+                return None;
+            }
+        }
+    }
+
+    if let Some(values) = matches.values_of("keyword") {
+        config.keywords.clear();
+        for value in values {
+            match KeywordConfig::from_string(value) {
+                Some(keyword) => config.keywords.push(keyword),
+                None => {
+                    print_error(&format!("Invalid keyword \"{}\".", value));
+                    return None;
+                }
+            }
+        }
+    }
+
+    for value in matches.values_of("template").into_iter().flatten() {
+        let mut split = value.splitn(2, '=');
+        let name = split.next().unwrap_or("").trim();
+        let template = match split.next() {
+            Some(template) => template.replace("\\n", "\n"),
+            None => {
+                print_error(&format!(
+                    "Invalid template \"{}\". Expected KEYWORD=TEMPLATE.",
+                    value
+                ));
+                return None;
+            }
+        };
+        if config.keywords.is_empty() {
+            config
+                .keywords
+                .push(KeywordConfig::new(keyword::DEFAULT_KEYWORD));
+        }
+        match config.keywords.iter_mut().find(|k| k.name == name) {
+            Some(keyword) => keyword.template = Some(template),
+            None => {
+                print_error(&format!(
+                    "Template given for unknown keyword \"{}\".",
+                    name
+                ));
+                return None;
+            }
+        }
+    }
+
+    Some(())
+}
+
+fn parse_patterns(patterns: &[String]) -> Option<Vec<Pattern>> {
+    //! Compiles glob patterns, outputting an error for an invalid one.
+    let mut compiled = Vec::new();
+    for pattern in patterns {
+        match Pattern::new(pattern) {
+            Ok(pattern) => compiled.push(pattern),
+            Err(e) => {
+                print_error(&format!(
+                    "Invalid glob pattern \"{}\": {}",
+                    pattern, e
+                ));
+                return None;
+            }
+        }
+    }
+
+    Some(compiled)
 }
 
 pub fn output_issues_and_prompt_user(
-    request: &Request,
+    tracker: &dyn IssueTracker,
     map: &HashMap<String, Vec<Issue>>,
-) {
+    sort: Sort,
+) -> bool {
     //! Outputs every todo comment found and prompts the user for action.
     //!
     //! Allows the user to
@@ -122,49 +725,184 @@ pub fn output_issues_and_prompt_user(
     //! - Edit the body or title before opening
     //! - Skip to the next one
     //! - Exit the program
-    for (_file, issues) in map {
-        for issue in issues {
-            println!("\n{}", &issue.to_formatted_string());
-
-            let prompt =
-                format!("{}", style("What would you like to do?").italic())
-                    .to_string();
-            let selection = Select::with_theme(&ColorfulTheme::default())
-                .with_prompt(&prompt)
-                .default(0)
-                .items(&SELECTIONS[..])
-                .interact()
-                .unwrap();
-
-            let done = match selection {
-                OPEN => open_issue(&request, &issue),
-                EDIT => edit_issue(&request, &issue),
-                SKIP => false,
-                _ => true,
-            };
-            if done {
-                return;
-            }
+    //!
+    //! Returns false and outputs an error if there's no terminal to prompt in.
+    if !is_interactive() {
+        print_error(
+            "Not running in a terminal. \
+             Use --yes to open every issue without prompting.",
+        );
+        return false;
+    }
+
+    for (_, issue) in sort_issues(map, sort) {
+        print_info(&format!("\n{}", &issue.to_formatted_string()));
+
+        let prompt =
+            format!("{}", style("What would you like to do?").italic())
+                .to_string();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(&prompt)
+            .default(0)
+            .items(SELECTIONS)
+            .interact()
+            .unwrap();
+
+        let done = match selection {
+            OPEN => open_issue(tracker, issue),
+            EDIT => edit_issue(tracker, issue),
+            SKIP => false,
+            _ => true,
+        };
+        if done {
+            return true;
         }
     }
 
     print_success("All done!");
+    true
+}
+
+pub fn open_all_issues(
+    tracker: &dyn IssueTracker,
+    map: &HashMap<String, Vec<Issue>>,
+    max_issues: usize,
+    sort: Sort,
+) -> bool {
+    //! Opens every todo comment found as an issue without prompting,
+    //! refusing to open any if there are more than max_issues of them.
+    //! Outputs a summary table of the issues opened.
+    //!
+    //! Returns a bool indicating whether every issue was opened.
+    let count: usize = map.values().map(|issues| issues.len()).sum();
+    if count > max_issues {
+        print_error(&format!(
+            "Found {} new {}, more than the maximum of {}. \
+             No issues were opened. Raise the limit with --max-issues.",
+            count,
+            handle_plural(&count, "issue"),
+            max_issues
+        ));
+        return false;
+    }
+
+    let mut rows = Vec::new();
+    let mut failed = 0;
+    for (file, issue) in sort_issues(map, sort) {
+        let (status, number) = match tracker.open_issue(issue) {
+            Some(n) => ("Opened", tracker.format_number(n)),
+            None => {
+                failed += 1;
+                ("Failed", "-".to_string())
+            }
+        };
+        rows.push([
+            status.to_string(),
+            number,
+            file.clone(),
+            issue.get_title(),
+        ]);
+    }
+
+    print_table(&["Status", "Issue", "File", "Title"], &rows);
+    match failed {
+        0 => {
+            print_success(&format!(
+                "Opened {} {}.",
+                rows.len(),
+                handle_plural(&rows.len(), "issue")
+            ));
+            true
+        }
+        n => {
+            print_error(&format!(
+                "Failed to open {} of {} {}.",
+                n,
+                rows.len(),
+                handle_plural(&rows.len(), "issue")
+            ));
+            false
+        }
+    }
+}
+
+fn sort_issues(
+    map: &HashMap<String, Vec<Issue>>,
+    sort: Sort,
+) -> Vec<(&String, &Issue)> {
+    //! Orders the issues of every file by path and line, or from the oldest
+    //! to the newest todo with the path and line breaking ties. Uncommitted
+    //! todos are the newest.
+    let mut issues: Vec<(&String, &Issue)> = map
+        .iter()
+        .flat_map(|(file, issues)| {
+            issues.iter().map(move |issue| (file, issue))
+        })
+        .collect();
+    issues.sort_by_key(|(file, issue)| {
+        let time = match sort {
+            Sort::Age => issue.get_blame().map_or(i64::MAX, |b| b.time),
+            Sort::File => 0,
+        };
+        (time, *file, issue.get_location().line)
+    });
+
+    issues
+}
+
+fn print_table(headers: &[&str; 4], rows: &[[String; 4]]) {
+    //! Outputs the rows as columns padded to their widest cell.
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    print_info("");
+    print_info(&style(format_row(headers.to_vec())).bold().to_string());
+    for row in rows {
+        print_info(&format_row(row.iter().map(|c| c.as_str()).collect()));
+    }
+}
+
+pub fn is_interactive() -> bool {
+    //! Returns whether stdin is a terminal the user can answer prompts in.
+    io::stdin().is_terminal()
+}
+
+pub fn print_info(msg: &str) {
+    //! Prints output meant for humans, to stderr if stdout is reserved for
+    //! machine readable output.
+    match IS_STDOUT_RESERVED.load(Ordering::Relaxed) {
+        true => eprintln!("{}", msg),
+        false => println!("{}", msg),
+    }
 }
 
 pub fn print_success(msg: &str) {
-    println!("{}", style(msg).green());
+    print_info(&style(msg).green().to_string());
 }
 
 pub fn print_warning(msg: &str) {
-    println!("{}", style(msg).yellow());
+    print_info(&style(msg).yellow().to_string());
 }
 
 pub fn print_error(msg: &str) {
-    println!("{} {}", style("ERROR:").red(), style(msg).red());
+    print_info(&format!("{} {}", style("ERROR:").red(), style(msg).red()));
 }
 
 pub fn print_dim(msg: &str) {
-    println!("{}", style(msg).dim());
+    print_info(&style(msg).dim().to_string());
 }
 
 pub fn handle_plural(number: &usize, word: &str) -> String {
@@ -174,20 +912,20 @@ pub fn handle_plural(number: &usize, word: &str) -> String {
     }
 }
 
-fn edit_issue(request: &Request, issue: &Issue) -> bool {
+fn edit_issue(tracker: &dyn IssueTracker, issue: &Issue) -> bool {
     //! Opens the user's default editor and allows them to edit an issue's
     //! title and body before opening it.
     //!
     //! Creates an issue on GitHub if the format is valid
-    //! (see Issue::from_string) and the user saves and quits.
+    //! (see Issue::edit) and the user saves and quits.
     //! Aborts the operation if the user exits without saving.
-    //! //! Returns a bool indicating whether or not to terminate the program.
+    //! Returns a bool indicating whether or not to terminate the program.
     let result = Editor::new().edit(&issue.to_string()).unwrap();
 
     match result {
-        Some(input) => match Issue::from_string(input) {
+        Some(input) => match issue.edit(input) {
             Some(new_issue) => {
-                return open_issue(request, &new_issue);
+                return open_issue(tracker, &new_issue);
             }
             None => print_warning("INvalid format. Not creating issue."),
         },
@@ -199,14 +937,14 @@ fn edit_issue(request: &Request, issue: &Issue) -> bool {
     false
 }
 
-fn open_issue(request: &Request, issue: &Issue) -> bool {
-    //! Creates the GitHub issue and outputs the result.
+fn open_issue(tracker: &dyn IssueTracker, issue: &Issue) -> bool {
+    //! Creates the issue in the tracker and outputs the result.
     //! Returns a bool indicating whether or not to terminate the program.
-    match request.open_issue(issue) {
+    match tracker.open_issue(issue) {
         Some(issue_number) => {
             let success_msg = format!(
-                "Successfully opened issue #{}: \"{}\"",
-                issue_number,
+                "Successfully opened issue {}: \"{}\"",
+                tracker.format_number(issue_number),
                 issue.get_title()
             )
             .to_string();
@@ -1,13 +1,22 @@
+use std::collections::HashMap;
+use std::ops::Range;
 use std::process::Command;
 use std::str;
 
-use dialoguer::PasswordInput;
+use dialoguer::{theme::ColorfulTheme, PasswordInput, Select};
 
 use super::cli;
+use super::issue;
+
+use issue::Blame;
+
+pub const GITHUB_HOST: &str = "github.com";
+const GITLAB_HOST: &str = "gitlab.com";
+const DEFAULT_REMOTE: &str = "origin";
 
 pub fn read_access_token() -> String {
-    //! Reads in a user's personal access token from GutHub.
-    println!("Please paste your personal access token from GitHub below.");
+    //! Reads in a user's personal access token for the issue tracker.
+    cli::print_info("Please paste your personal access token below.");
     PasswordInput::new()
         .with_prompt("Token")
         .interact()
@@ -25,38 +34,226 @@ pub fn is_git_repo() -> bool {
     !output.is_empty()
 }
 
-pub fn get_remote_name(is_dry_run: bool) -> Option<String> {
-    //! Executes the command `git remote get-url origin`.
-    //! Parses the result to return a string of the form :username/:repo
-    //! if successful. Otherwise, returns None if there is no remote.
+pub fn get_repo_root() -> String {
+    //! Executes the command `git rev-parse --show-toplevel`.
+    //! Returns the output which represents the root of the repository.
     let command = Command::new("git")
-        .arg("remote")
-        .arg("get-url")
-        .arg("origin")
+        .arg("rev-parse")
+        .arg("--show-toplevel")
         .output()
-        .expect("Failed to execute `git remote get-url origin`");
+        .expect("Failed to execute `git rev-parse --show-toplevel`");
     let output = str::from_utf8(&command.stdout).unwrap();
-    // Output is of the form https://github.com/:username/:repo.git
-    // So we must remove the protocol/domain and .git suffix.
-    let split: Vec<&str> = output.split("github.com/").collect();
-    if split.len() < 2 {
-        match is_dry_run {
-            true => {
-                cli::print_warning(
-                    "No remote found. Searching for TODOs anyways.",
-                );
-                return Some(String::new());
+
+    output.trim().to_string()
+}
+
+#[derive(Default)]
+pub struct Remote {
+    pub host: String,
+    pub path: String,
+}
+
+pub struct Change {
+    pub commit: String,
+    pub path: String,
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+}
+
+pub fn get_remote(
+    remote: Option<&str>,
+    api_url: Option<&str>,
+    is_dry_run: bool,
+) -> Option<Remote> {
+    //! Finds the repository to open issues in, using the given git remote
+    //! or, if none is given, the remote pointing to GitHub, GitLab or the
+    //! host of the configured API URL. Falls back to `origin` so
+    //! self-hosted remotes work without any configuration. Prompts the user
+    //! to choose if several remotes match, e.g. a fork and its upstream,
+    //! or outputs an error listing them if there's no terminal to prompt in.
+    //!
+    //! Returns the host and :username/:repo path of the remote if
+    //! successful. Otherwise, returns None if there is no such remote.
+    let remotes: Vec<(String, Remote)> = match remote {
+        Some(name) => match get_remote_url(name) {
+            Some(url) => match parse_remote_url(&url) {
+                Some(remote) => vec![(name.to_string(), remote)],
+                None => {
+                    return handle_missing_remote(
+                        &format!(
+                            "Remote \"{}\" has an unknown URL format.",
+                            name
+                        ),
+                        is_dry_run,
+                    )
+                }
+            },
+            None => {
+                return handle_missing_remote(
+                    &format!("No remote named \"{}\" found.", name),
+                    is_dry_run,
+                )
+            }
+        },
+        None => {
+            let mut hosts =
+                vec![GITHUB_HOST.to_string(), GITLAB_HOST.to_string()];
+            hosts.extend(api_url.and_then(get_url_host));
+            let remotes: Vec<(String, Remote)> = get_remote_names()
+                .into_iter()
+                .filter_map(|name| {
+                    let remote = parse_remote_url(&get_remote_url(&name)?)?;
+                    Some((name, remote))
+                })
+                .collect();
+
+            match remotes.iter().any(|(_, r)| hosts.contains(&r.host)) {
+                true => remotes
+                    .into_iter()
+                    .filter(|(_, remote)| hosts.contains(&remote.host))
+                    .collect(),
+                false => remotes
+                    .into_iter()
+                    .filter(|(name, _)| name == DEFAULT_REMOTE)
+                    .collect(),
             }
-            false => {
-                cli::print_error("No remote found.");
+        }
+    };
+
+    match remotes.len() {
+        0 => handle_missing_remote("No remote found.", is_dry_run),
+        1 => remotes.into_iter().next().map(|(_, remote)| remote),
+        // The remote doesn't matter on a dry run, so don't bother asking.
+        _ if is_dry_run => remotes.into_iter().next().map(|(_, remote)| remote),
+        _ => {
+            let items: Vec<String> = remotes
+                .iter()
+                .map(|(name, remote)| {
+                    format!("{} ({}/{})", name, remote.host, remote.path)
+                })
+                .collect();
+            if !cli::is_interactive() {
+                cli::print_error(&format!(
+                    "Found several remotes: {}. Choose one with --remote.",
+                    items.join(", ")
+                ));
                 return None;
             }
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Which remote should issues be opened in?")
+                .default(0)
+                .items(&items)
+                .interact()
+                .unwrap();
+
+            remotes.into_iter().nth(selection).map(|(_, remote)| remote)
+        }
+    }
+}
+
+fn handle_missing_remote(msg: &str, is_dry_run: bool) -> Option<Remote> {
+    //! Outputs why no remote could be used. A dry run doesn't need one, so
+    //! it carries on with an empty remote.
+    match is_dry_run {
+        true => {
+            cli::print_warning(&format!(
+                "{} Searching for TODOs anyways.",
+                msg
+            ));
+            Some(Remote {
+                host: GITHUB_HOST.to_string(),
+                path: String::new(),
+            })
+        }
+        false => {
+            cli::print_error(msg);
+            None
+        }
+    }
+}
+
+fn get_remote_names() -> Vec<String> {
+    //! Executes the command `git remote`.
+    //! Returns the names of every remote of the repository.
+    let command = Command::new("git")
+        .arg("remote")
+        .output()
+        .expect("Failed to execute `git remote`");
+    let output = str::from_utf8(&command.stdout).unwrap();
+
+    output
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn get_remote_url(remote: &str) -> Option<String> {
+    //! Executes the command `git remote get-url {remote}`.
+    //! Returns the URL of the remote or None if it doesn't exist.
+    let command = Command::new("git")
+        .arg("remote")
+        .arg("get-url")
+        .arg(remote)
+        .output()
+        .expect("Failed to execute `git remote get-url`");
+    let output = str::from_utf8(&command.stdout).unwrap().trim();
+
+    match output.is_empty() {
+        true => None,
+        false => Some(output.to_string()),
+    }
+}
+
+fn parse_remote_url(url: &str) -> Option<Remote> {
+    //! Splits a remote URL into its host and repository path, e.g.
+    //! `git@github.com:owner/repo.git` into `github.com` and `owner/repo`.
+    //! Understands https, ssh, `ssh://` and `git://` URLs, with or without
+    //! the .git suffix.
+    //!
+    //! Returns None if the URL isn't of a known form.
+    let (host, path) = match url.find("://") {
+        Some(index) => {
+            let rest = &url[index + 3..];
+            let slash = rest.find('/')?;
+            (get_url_host(url)?, &rest[slash + 1..])
         }
+        // scp-like syntax used by ssh, e.g. git@github.com:owner/repo.git
+        None => {
+            let colon = url.find(':')?;
+            (strip_user_and_port(&url[..colon])?, &url[colon + 1..])
+        }
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if path.split('/').filter(|s| !s.is_empty()).count() < 2 {
+        return None;
     }
-    let remote = split[1].to_string();
-    let vec: Vec<&str> = remote.split(".git").collect();
 
-    Some(vec[0].to_string())
+    Some(Remote {
+        host,
+        path: path.to_string(),
+    })
+}
+
+fn get_url_host(url: &str) -> Option<String> {
+    //! Returns the host of a URL of the form scheme://[user@]host[:port]/..
+    let rest = &url[url.find("://")? + 3..];
+    let authority = rest.split('/').next()?;
+
+    strip_user_and_port(authority)
+}
+
+fn strip_user_and_port(authority: &str) -> Option<String> {
+    //! Reduces e.g. `git@github.com:22` to `github.com`.
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?.to_lowercase();
+
+    match host.is_empty() {
+        true => None,
+        false => Some(host),
+    }
 }
 
 pub fn get_tracked_files() -> Vec<String> {
@@ -81,7 +278,223 @@ pub fn get_tracked_files() -> Vec<String> {
         .collect()
 }
 
-fn get_branch_name() -> String {
+pub fn get_files_at(reference: &str) -> Option<Vec<String>> {
+    //! Executes the command `git ls-tree -r {reference} --name-only`.
+    //! Returns the paths of every file in the tree of the commit, or None
+    //! if the reference can't be resolved.
+    let command = Command::new("git")
+        .arg("ls-tree")
+        .arg("-r")
+        .arg(reference)
+        .arg("--name-only")
+        .output()
+        .expect("Failed to execute `git ls-tree`");
+    if !command.status.success() {
+        return None;
+    }
+    let output = str::from_utf8(&command.stdout).ok()?;
+
+    Some(
+        output
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+    )
+}
+
+pub fn read_file_at(reference: &str, path: &str) -> Option<String> {
+    //! Executes the command `git show {reference}:./{path}`, with the path
+    //! relative to the current directory like the ones git ls-tree outputs.
+    //! Returns the contents of the file in the commit, or None if it
+    //! doesn't exist there or isn't text.
+    let command = Command::new("git")
+        .arg("show")
+        .arg(format!("{}:./{}", reference, path))
+        .output()
+        .expect("Failed to execute `git show`");
+    if !command.status.success() {
+        return None;
+    }
+
+    String::from_utf8(command.stdout).ok()
+}
+
+fn get_merge_base(reference: &str) -> Option<String> {
+    //! Executes the command `git merge-base {reference} HEAD`.
+    //! Returns the sha of the commit the current branch forked from, or
+    //! None if the reference can't be resolved.
+    let command = Command::new("git")
+        .arg("merge-base")
+        .arg(reference)
+        .arg("HEAD")
+        .output()
+        .expect("Failed to execute `git merge-base`");
+    let output = str::from_utf8(&command.stdout).ok()?.trim();
+
+    match command.status.success() && !output.is_empty() {
+        true => Some(output.to_string()),
+        false => None,
+    }
+}
+
+pub fn get_added_lines(
+    reference: &str,
+) -> Option<HashMap<String, Vec<Range<u32>>>> {
+    //! Compares the working tree to the commit the current branch forked
+    //! from the reference at.
+    //! Returns the ranges of line numbers added or changed since, per file
+    //! path.
+    //!
+    //! Returns None and outputs an error if the reference can't be resolved.
+    let commit = match get_merge_base(reference) {
+        Some(commit) => commit,
+        None => {
+            cli::print_error(&format!(
+                "Unknown git reference \"{}\", or it shares no history \
+                 with HEAD.",
+                reference
+            ));
+            return None;
+        }
+    };
+
+    let mut added: HashMap<String, Vec<Range<u32>>> = HashMap::new();
+    for (file, range) in get_hunks(&commit) {
+        if !range.is_empty() {
+            added.entry(file).or_default().push(range);
+        }
+    }
+
+    Some(added)
+}
+
+pub fn get_first_changed_lines() -> HashMap<String, u32> {
+    //! Executes the command `git diff --unified=0 --relative HEAD`.
+    //! Returns the first line of each file in the working tree that differs
+    //! from the current commit, per file path. Lines before it have the
+    //! same number in both.
+    let mut first: HashMap<String, u32> = HashMap::new();
+    for (file, range) in get_hunks("HEAD") {
+        // Lines removed after line n shift the lines from n + 1 on.
+        let line = match range.is_empty() {
+            true => range.start + 1,
+            false => range.start,
+        };
+        let entry = first.entry(file).or_insert(line);
+        *entry = line.min(*entry);
+    }
+
+    first
+}
+
+fn get_hunks(commit: &str) -> Vec<(String, Range<u32>)> {
+    //! Executes the command `git diff --unified=0 --relative {commit}`,
+    //! comparing the working tree to the commit.
+    //! Returns the file path and range of new line numbers of every hunk.
+    let command = Command::new("git")
+        .arg("-c")
+        .arg("core.quotePath=false")
+        .arg("diff")
+        .arg("--unified=0")
+        .arg("--no-color")
+        .arg("--no-ext-diff")
+        .arg("--relative")
+        .arg("--src-prefix=a/")
+        .arg("--dst-prefix=b/")
+        .arg(commit)
+        .output()
+        .expect("Failed to execute `git diff`");
+    let output = String::from_utf8_lossy(&command.stdout);
+
+    let mut hunks = Vec::new();
+    let mut file = None;
+    for line in output.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            // Deleted files are compared to /dev/null.
+            file = path.strip_prefix("b/").map(|path| path.to_string());
+            continue;
+        }
+        if let (Some(file), Some(range)) = (&file, parse_hunk_header(line)) {
+            hunks.push((file.clone(), range));
+        }
+    }
+
+    hunks
+}
+
+fn parse_hunk_header(line: &str) -> Option<Range<u32>> {
+    //! Parses the new side of a hunk header like `@@ -10,2 +12,3 @@`,
+    //! where the count defaults to 1 if it's left out.
+    let new = line
+        .strip_prefix("@@ ")?
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('+'))?;
+    let mut split = new.splitn(2, ',');
+    let start: u32 = split.next()?.parse().ok()?;
+    let count: u32 = match split.next() {
+        Some(count) => count.parse().ok()?,
+        None => 1,
+    };
+
+    Some(start..start + count)
+}
+
+pub fn blame_lines(path: &str, lines: &[u32]) -> HashMap<u32, Blame> {
+    //! Executes the command `git blame --line-porcelain -L {n},{n} -- {path}`
+    //! with a range for every line, blaming the file in the working tree.
+    //! Returns the author and commit that last changed each line, leaving
+    //! out lines that aren't committed yet.
+    let mut command = Command::new("git");
+    command.arg("blame").arg("--line-porcelain");
+    for line in lines {
+        command.arg("-L").arg(format!("{},{}", line, line));
+    }
+    let output = match command.arg("--").arg(path).output() {
+        Ok(output) if output.status.success() => output,
+        _ => return HashMap::new(),
+    };
+    let output = String::from_utf8_lossy(&output.stdout);
+
+    let mut blames = HashMap::new();
+    let mut current: Option<(u32, Blame)> = None;
+    for line in output.lines() {
+        // Every blamed line ends with its contents, prefixed by a tab.
+        if line.starts_with('\t') {
+            if let Some((number, blame)) = current.take() {
+                if blame.is_committed() {
+                    blames.insert(number, blame);
+                }
+            }
+            continue;
+        }
+
+        let blame = match &mut current {
+            Some((_, blame)) => blame,
+            None => {
+                // The header is `{sha} {original line} {final line} ...`.
+                let mut split = line.split(' ');
+                let commit = split.next().unwrap_or("").to_string();
+                let number = split.nth(1).and_then(|n| n.parse().ok());
+                if let Some(number) = number {
+                    current = Some((number, Blame::new(commit)));
+                }
+                continue;
+            }
+        };
+        if let Some(author) = line.strip_prefix("author ") {
+            blame.author = author.to_string();
+        } else if let Some(email) = line.strip_prefix("author-mail ") {
+            blame.email = email.trim_matches(|c| c == '<' || c == '>').into();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            blame.time = time.parse().unwrap_or(0);
+        }
+    }
+
+    blames
+}
+
+pub fn get_branch_name() -> String {
     //! Executes the command `git rev-parse --abbrev-ref HEAD`.
     //! Returns the output which represents the current branch the user is on.
     let command = Command::new("git")
@@ -93,4 +506,177 @@ fn get_branch_name() -> String {
     let output = str::from_utf8(&command.stdout).unwrap();
 
     output.trim().to_string()
-}
\ No newline at end of file
+}
+
+pub fn get_path_prefix() -> String {
+    //! Executes the command `git rev-parse --show-prefix`.
+    //! Returns the output which represents the path of the current
+    //! directory relative to the root of the repository, e.g. `src/`.
+    let command = Command::new("git")
+        .arg("rev-parse")
+        .arg("--show-prefix")
+        .output()
+        .expect("Failed to execute `git rev-parse --show-prefix`");
+    let output = str::from_utf8(&command.stdout).unwrap();
+
+    output.trim().to_string()
+}
+
+pub fn get_head_commit() -> String {
+    //! Executes the command `git rev-parse HEAD`.
+    //! Returns the output which represents the sha of the current commit.
+    let command = Command::new("git")
+        .arg("rev-parse")
+        .arg("HEAD")
+        .output()
+        .expect("Failed to execute `git rev-parse HEAD`");
+    let output = str::from_utf8(&command.stdout).unwrap();
+
+    output.trim().to_string()
+}
+
+pub fn get_changes(pattern: &str) -> Vec<Change> {
+    //! Executes the command `git log -p --unified=0 --relative -G {pattern}`.
+    //! Parses the output to return the lines removed from and added to each
+    //! file by every commit whose changes match the regular expression,
+    //! newest first.
+    let command = Command::new("git")
+        .arg("-c")
+        .arg("core.quotePath=false")
+        .arg("log")
+        .arg("-p")
+        .arg("--unified=0")
+        .arg("--no-color")
+        .arg("--no-ext-diff")
+        .arg("--relative")
+        .arg("--src-prefix=a/")
+        .arg("--dst-prefix=b/")
+        .arg("--format=commit %H")
+        .arg("-G")
+        .arg(pattern)
+        .output()
+        .expect("Failed to execute `git log -p`");
+    let output = String::from_utf8_lossy(&command.stdout);
+
+    let mut changes: Vec<Change> = Vec::new();
+    let mut commit = String::new();
+    // File headers run from `diff --git` to the first hunk header.
+    let mut is_header = false;
+    for line in output.lines() {
+        if let Some(sha) = line.strip_prefix("commit ") {
+            commit = sha.to_string();
+            is_header = false;
+        } else if line.starts_with("diff --git ") {
+            changes.push(Change {
+                commit: commit.clone(),
+                path: String::new(),
+                removed: Vec::new(),
+                added: Vec::new(),
+            });
+            is_header = true;
+        } else if line.starts_with("@@ ") {
+            is_header = false;
+        } else if let Some(change) = changes.last_mut() {
+            if is_header {
+                // Added and deleted files are compared to /dev/null.
+                let path = line
+                    .strip_prefix("--- a/")
+                    .or_else(|| line.strip_prefix("+++ b/"));
+                if let Some(path) = path {
+                    change.path = path.to_string();
+                }
+            } else if let Some(removed) = line.strip_prefix('-') {
+                change.removed.push(removed.to_string());
+            } else if let Some(added) = line.strip_prefix('+') {
+                change.added.push(added.to_string());
+            }
+        }
+    }
+
+    changes
+}
+
+pub fn contains_text(reference: &str, text: &str) -> bool {
+    //! Executes the command `git grep -F -q -e {text} {reference}`.
+    //! Returns whether any file of the commit below the current directory
+    //! contains the text, or false if the reference doesn't exist.
+    Command::new("git")
+        .arg("grep")
+        .arg("-F")
+        .arg("-q")
+        .arg("-e")
+        .arg(text)
+        .arg(reference)
+        .arg("--")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Option<(String, String)> {
+        parse_remote_url(url).map(|remote| (remote.host, remote.path))
+    }
+
+    fn remote(host: &str, path: &str) -> Option<(String, String)> {
+        Some((host.to_string(), path.to_string()))
+    }
+
+    #[test]
+    fn parses_https_urls() {
+        assert_eq!(
+            parse("https://github.com/owner/repo.git"),
+            remote("github.com", "owner/repo")
+        );
+        assert_eq!(
+            parse("https://user@GitLab.com:443/group/sub/repo/"),
+            remote("gitlab.com", "group/sub/repo")
+        );
+    }
+
+    #[test]
+    fn parses_ssh_urls() {
+        assert_eq!(
+            parse("ssh://git@github.com:22/owner/repo.git"),
+            remote("github.com", "owner/repo")
+        );
+        assert_eq!(
+            parse("git@github.com:owner/repo.git"),
+            remote("github.com", "owner/repo")
+        );
+        assert_eq!(
+            parse("gitea.example.com:owner/repo"),
+            remote("gitea.example.com", "owner/repo")
+        );
+    }
+
+    #[test]
+    fn parses_git_urls() {
+        assert_eq!(
+            parse("git://example.com/owner/repo.git"),
+            remote("example.com", "owner/repo")
+        );
+    }
+
+    #[test]
+    fn rejects_urls_without_a_repository() {
+        assert_eq!(parse("https://github.com/owner"), None);
+        assert_eq!(parse("git@github.com:repo.git"), None);
+        assert_eq!(parse("/local/path/repo"), None);
+        assert_eq!(parse("@:owner/repo"), None);
+    }
+
+    #[test]
+    fn parses_hunk_headers() {
+        assert_eq!(
+            parse_hunk_header("@@ -10,2 +12,3 @@ fn main()"),
+            Some(12..15)
+        );
+        assert_eq!(parse_hunk_header("@@ -10 +12 @@"), Some(12..13));
+        assert_eq!(parse_hunk_header("@@ -10,2 +9,0 @@"), Some(9..9));
+        assert_eq!(parse_hunk_header("@@@ -1 -1 +1 @@@"), None);
+        assert_eq!(parse_hunk_header("+++ b/src/main.rs"), None);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::body;
+use super::keyword;
+
+use keyword::Keyword;
+
+pub const FILE_NAME: &str = ".todo-issue.toml";
+const DEFAULT_JIRA_ISSUE_TYPE: &str = "Task";
+const DEFAULT_LOCAL_DIR: &str = ".todos";
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tracker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet_lines: Option<usize>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub labels: Vec<String>,
+    pub assignees: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<String>,
+    pub keywords: Vec<KeywordConfig>,
+    // Maps the emails of commit authors to logins to assign issues to.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub authors: BTreeMap<String, String>,
+    // Maps glob patterns to labels added to issues of the matching files.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub path_labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jira: Option<JiraConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub check: Option<CheckConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeywordConfig {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_prefix: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct JiraConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub project: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CheckConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+impl Config {
+    pub fn load(root: &str) -> Result<Config, String> {
+        //! Reads the config file from the root of the repository.
+        //!
+        //! Returns the default config if there is no config file and an
+        //! error message if the file can't be read or parsed.
+        let path = Path::new(root).join(FILE_NAME);
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", FILE_NAME, e))?;
+        toml::from_str(&contents)
+            .map_err(|e| format!("Invalid {}: {}", FILE_NAME, e))
+    }
+
+    pub fn get_remote(&self) -> Option<&str> {
+        self.remote.as_deref()
+    }
+
+    pub fn get_api_url(&self) -> Option<&str> {
+        self.api_url.as_deref()
+    }
+
+    pub fn get_tracker(&self) -> Option<&str> {
+        self.tracker.as_deref()
+    }
+
+    pub fn get_local_dir(&self) -> &str {
+        //! Returns the directory of the local issue store, relative to the
+        //! root of the repository.
+        self.local
+            .as_ref()
+            .and_then(|local| local.dir.as_deref())
+            .unwrap_or(DEFAULT_LOCAL_DIR)
+    }
+
+    pub fn get_baseline(&self) -> Option<&str> {
+        //! Returns the path of the check's baseline file, relative to the
+        //! root of the repository.
+        self.check.as_ref()?.baseline.as_deref()
+    }
+
+    pub fn get_max(&self) -> Option<usize> {
+        self.check.as_ref()?.max
+    }
+
+    pub fn get_snippet_lines(&self) -> usize {
+        //! Returns the number of lines of code shown before and after a
+        //! todo in the body of its issue.
+        self.snippet_lines.unwrap_or(body::DEFAULT_SNIPPET_LINES)
+    }
+
+    pub fn get_keywords(&self) -> Vec<Keyword> {
+        //! Converts the configured keywords into Keywords, defaulting to
+        //! TODO if none are configured. Keywords without templates or a
+        //! title prefix of their own use the config's.
+        let configs = match self.keywords.is_empty() {
+            true => vec![KeywordConfig::new(keyword::DEFAULT_KEYWORD)],
+            false => self.keywords.clone(),
+        };
+
+        configs
+            .into_iter()
+            .map(|config| {
+                let mut keyword = Keyword::new(&config.name);
+                if let Some(label) = config.label {
+                    keyword.set_label(label);
+                }
+                if let Some(template) =
+                    config.template.or_else(|| self.body_template.clone())
+                {
+                    keyword.set_template(template);
+                }
+                if let Some(template) = config
+                    .title_template
+                    .or_else(|| self.title_template.clone())
+                {
+                    keyword.set_title_template(template);
+                }
+                if let Some(prefix) =
+                    config.title_prefix.or_else(|| self.title_prefix.clone())
+                {
+                    keyword.set_title_prefix(prefix);
+                }
+                keyword
+            })
+            .collect()
+    }
+
+    pub fn to_effective(&self) -> Config {
+        //! Returns a copy of the config with every default filled in,
+        //! used to show the settings a run would use.
+        let keywords = self
+            .get_keywords()
+            .iter()
+            .map(|keyword| KeywordConfig {
+                name: keyword.get_name().to_string(),
+                label: Some(keyword.get_label().to_string()),
+                template: keyword.get_template().map(|t| t.to_string()),
+                title_template: keyword
+                    .get_title_template()
+                    .map(|t| t.to_string()),
+                title_prefix: keyword.get_title_prefix().map(|p| p.to_string()),
+            })
+            .collect();
+
+        Config {
+            remote: self.remote.clone(),
+            api_url: self.api_url.clone(),
+            tracker: self.tracker.clone(),
+            body_template: self.body_template.clone(),
+            title_template: self.title_template.clone(),
+            title_prefix: self.title_prefix.clone(),
+            snippet_lines: Some(self.get_snippet_lines()),
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            labels: self.labels.clone(),
+            assignees: self.assignees.clone(),
+            milestone: self.milestone.clone(),
+            keywords,
+            authors: self.authors.clone(),
+            path_labels: self.path_labels.clone(),
+            jira: self.jira.clone().map(|jira| JiraConfig {
+                issue_type: Some(jira.get_issue_type().to_string()),
+                ..jira
+            }),
+            local: self.local.clone(),
+            check: self.check.clone(),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl JiraConfig {
+    pub fn get_issue_type(&self) -> &str {
+        self.issue_type
+            .as_deref()
+            .unwrap_or(DEFAULT_JIRA_ISSUE_TYPE)
+    }
+}
+
+impl KeywordConfig {
+    pub fn new(name: &str) -> KeywordConfig {
+        KeywordConfig {
+            name: name.to_string(),
+            label: None,
+            template: None,
+            title_template: None,
+            title_prefix: None,
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<KeywordConfig> {
+        //! Parses a keyword of the form `NAME` or `NAME=LABEL`.
+        //!
+        //! Returns None if the name or label is empty.
+        let mut split = string.splitn(2, '=');
+        let name = split.next()?.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut config = KeywordConfig::new(name);
+        if let Some(label) = split.next() {
+            let label = label.trim();
+            if label.is_empty() {
+                return None;
+            }
+            config.label = Some(label.to_string());
+        }
+
+        Some(config)
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::issue;
+
+use issue::{Issue, Response};
+
+const MARKER_PREFIX: &str = "<!-- todo-issue fingerprint: ";
+const MARKER_SUFFIX: &str = " -->";
+
+// Parameters of the 64 bit FNV-1a hash, which unlike the standard library's
+// hasher is guaranteed to stay the same across Rust versions.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Fingerprint {
+    text: u64,
+    context: u64,
+}
+
+impl Fingerprint {
+    pub fn new(
+        keyword: &str,
+        title: &str,
+        path: &str,
+        context: &[&str],
+    ) -> Fingerprint {
+        //! Creates a fingerprint made of two halves:
+        //! - a hash of the keyword and normalized title, which stays the
+        //!   same when the comment moves within or between files
+        //! - a hash of the file path and normalized surrounding lines, which
+        //!   stays the same when the comment's text is edited
+        //!
+        //! Together they tell apart identical comments in different places.
+        let text = hash(&[keyword, &normalize(title)]);
+        let mut parts = vec![path.to_string()];
+        parts.extend(context.iter().map(|line| normalize(line)));
+        let parts: Vec<&str> = parts.iter().map(|part| part.as_str()).collect();
+
+        Fingerprint {
+            text,
+            context: hash(&parts),
+        }
+    }
+
+    pub fn from_body(body: &str) -> Option<Fingerprint> {
+        //! Parses the hidden fingerprint marker out of an issue body.
+        let start = body.find(MARKER_PREFIX)? + MARKER_PREFIX.len();
+        let length = body[start..].find(MARKER_SUFFIX)?;
+
+        Fingerprint::parse(&body[start..start + length])
+    }
+
+    pub fn parse(value: &str) -> Option<Fingerprint> {
+        //! Parses a fingerprint in the hexadecimal form it's displayed in.
+        let value = value.trim();
+        if value.len() != 32 || !value.is_ascii() {
+            return None;
+        }
+
+        Some(Fingerprint {
+            text: u64::from_str_radix(&value[..16], 16).ok()?,
+            context: u64::from_str_radix(&value[16..], 16).ok()?,
+        })
+    }
+
+    pub fn has_same_text(&self, other: &Fingerprint) -> bool {
+        //! Returns whether both fingerprints are of comments with the same
+        //! keyword and normalized title, wherever they are.
+        self.text == other.text
+    }
+
+    pub fn to_marker(self) -> String {
+        //! Returns the fingerprint as an HTML comment, which GitHub doesn't
+        //! render, to be stored in the issue body.
+        format!("{}{}{}", MARKER_PREFIX, self, MARKER_SUFFIX)
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.text, self.context)
+    }
+}
+
+pub fn match_existing(issues: &mut [&mut Issue], existing: &[Response]) {
+    //! Marks every issue that was already opened in the remote repo with
+    //! the number of the matching remote issue.
+    //!
+    //! Issues are matched on the full fingerprint first. Remaining issues
+    //! match a remote issue with the same text or context half of the
+    //! fingerprint, as long as exactly one unclaimed remote issue has it.
+    //! Remote issues opened before fingerprints existed are matched by title.
+    let mut by_fingerprint = HashMap::new();
+    let mut by_text: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut by_context: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut by_title = HashMap::new();
+    for response in existing {
+        match response.get_fingerprint() {
+            Some(fingerprint) => {
+                by_fingerprint.insert(fingerprint, response.get_number());
+                by_text
+                    .entry(fingerprint.text)
+                    .or_default()
+                    .push(response.get_number());
+                by_context
+                    .entry(fingerprint.context)
+                    .or_default()
+                    .push(response.get_number());
+            }
+            None => {
+                by_title.insert(response.get_title(), response.get_number());
+            }
+        }
+    }
+
+    // Todos referencing an issue, e.g. `TODO(#42)`, are matched already.
+    let mut claimed: Vec<usize> = issues
+        .iter()
+        .filter_map(|issue| issue.get_existing())
+        .collect();
+    for issue in issues.iter_mut() {
+        if issue.get_existing().is_some() {
+            continue;
+        }
+        let number = by_fingerprint
+            .get(issue.get_fingerprint())
+            .or_else(|| by_title.get(&issue.get_title()));
+        if let Some(number) = number {
+            if !claimed.contains(number) {
+                claimed.push(*number);
+                issue.set_existing(*number);
+            }
+        }
+    }
+
+    for issue in issues.iter_mut() {
+        if issue.get_existing().is_some() {
+            continue;
+        }
+        let fingerprint = issue.get_fingerprint();
+        let candidates = [
+            by_text.get(&fingerprint.text),
+            by_context.get(&fingerprint.context),
+        ];
+        for numbers in candidates.iter().flatten() {
+            let unclaimed: Vec<&usize> = numbers
+                .iter()
+                .filter(|number| !claimed.contains(number))
+                .collect();
+            if let [number] = unclaimed.as_slice() {
+                claimed.push(**number);
+                issue.set_existing(**number);
+                break;
+            }
+        }
+    }
+}
+
+fn normalize(text: &str) -> String {
+    //! Lowercases the text and reduces it to words separated by single
+    //! spaces, so changes in punctuation and whitespace are ignored.
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect();
+
+    words.join(" ")
+}
+
+fn hash(parts: &[&str]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for part in parts {
+        // Hashing a separator keeps ["ab", "c"] and ["a", "bc"] apart.
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issue::{self, Annotations, Location};
+    use crate::keyword::Keyword;
+
+    fn fingerprint(title: &str, path: &str) -> Fingerprint {
+        Fingerprint::new("TODO", title, path, &["fn main() {", "}"])
+    }
+
+    fn todo(title: &str, path: &str, context: &[&str]) -> Issue {
+        let location = Location {
+            file: path.to_string(),
+            line: 1,
+            column: 0,
+        };
+        Issue::new(
+            title.to_string(),
+            String::new(),
+            &Keyword::new("TODO"),
+            location,
+            Annotations::default(),
+            Fingerprint::new("TODO", title, path, context),
+        )
+    }
+
+    fn remote(number: usize, title: &str, body: Option<&Issue>) -> Response {
+        let body = body
+            .map(|todo| issue::join_full_body("Body", *todo.get_fingerprint()));
+        Response::new(title.to_string(), number, body, true)
+    }
+
+    fn match_all(todos: &mut [Issue], existing: &[Response]) -> Vec<usize> {
+        //! Returns the number each todo matched, or 0 if none.
+        let mut todos: Vec<&mut Issue> = todos.iter_mut().collect();
+        match_existing(&mut todos, existing);
+        todos
+            .iter()
+            .map(|todo| todo.get_existing().unwrap_or(0))
+            .collect()
+    }
+
+    #[test]
+    fn hash_is_fnv_1a() {
+        assert_eq!(hash(&[]), FNV_OFFSET);
+        assert_eq!(hash(&["a"]), 0x089b_e207_b544_f1e4);
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn normalize_ignores_case_and_punctuation() {
+        assert_eq!(
+            normalize("  Fix the  Parser, later!"),
+            "fix the parser later"
+        );
+        assert_eq!(normalize("--"), "");
+    }
+
+    #[test]
+    fn halves_follow_text_and_place() {
+        let original = fingerprint("Fix the parser", "src/a.rs");
+        let reworded = fingerprint("fix the parser.", "src/a.rs");
+        let moved = fingerprint("Fix the parser", "src/b.rs");
+        let edited = fingerprint("Rewrite the parser", "src/a.rs");
+
+        assert_eq!(original, reworded);
+        assert!(original.has_same_text(&moved));
+        assert_ne!(original, moved);
+        assert!(!original.has_same_text(&edited));
+        assert_eq!(original.context, edited.context);
+    }
+
+    #[test]
+    fn parses_displayed_form_and_markers() {
+        let fingerprint = fingerprint("Fix the parser", "src/a.rs");
+        let value = fingerprint.to_string();
+
+        assert_eq!(value.len(), 32);
+        assert_eq!(Fingerprint::parse(&value), Some(fingerprint));
+        assert_eq!(Fingerprint::parse(&value[1..]), None);
+        assert_eq!(Fingerprint::parse(&"g".repeat(32)), None);
+
+        let body = format!("Body\n\n{}\n", fingerprint.to_marker());
+        assert_eq!(Fingerprint::from_body(&body), Some(fingerprint));
+        assert_eq!(Fingerprint::from_body("Body"), None);
+    }
+
+    #[test]
+    fn matches_full_fingerprints() {
+        let opened = todo("Fix it", "a.rs", &["x"]);
+        let existing = [remote(1, "Other", Some(&opened))];
+
+        assert_eq!(
+            match_all(&mut [todo("Fix it", "a.rs", &["x"])], &existing),
+            [1]
+        );
+        assert_eq!(
+            match_all(&mut [todo("Fix it", "b.rs", &["x"])], &existing),
+            [1]
+        );
+    }
+
+    #[test]
+    fn matches_moved_and_edited_comments() {
+        let existing = [
+            remote(1, "Fix it", Some(&todo("Fix it", "a.rs", &["x"]))),
+            remote(2, "Old", Some(&todo("Old", "b.rs", &["y"]))),
+        ];
+        let mut todos = [
+            todo("Fix it", "c.rs", &["z"]),
+            todo("Rewritten", "b.rs", &["y"]),
+        ];
+
+        assert_eq!(match_all(&mut todos, &existing), [1, 2]);
+    }
+
+    #[test]
+    fn refuses_ambiguous_partial_matches() {
+        let existing = [
+            remote(1, "Fix it", Some(&todo("Fix it", "a.rs", &["x"]))),
+            remote(2, "Fix it", Some(&todo("Fix it", "b.rs", &["y"]))),
+        ];
+
+        assert_eq!(
+            match_all(&mut [todo("Fix it", "c.rs", &["z"])], &existing),
+            [0]
+        );
+        // Once one of them matches in full, the other is the only candidate.
+        let mut todos = [
+            todo("Fix it", "c.rs", &["z"]),
+            todo("Fix it", "a.rs", &["x"]),
+        ];
+        assert_eq!(match_all(&mut todos, &existing), [2, 1]);
+    }
+
+    #[test]
+    fn matches_issues_without_fingerprints_by_title() {
+        let existing = [
+            remote(1, "Fix it", None),
+            Response::new("Old".to_string(), 2, Some("Body".to_string()), true),
+        ];
+        let mut todos = [
+            todo("Old", "a.rs", &["x"]),
+            todo("Fix it", "b.rs", &["y"]),
+            todo("fix it", "c.rs", &["z"]),
+        ];
+
+        assert_eq!(match_all(&mut todos, &existing), [2, 1, 0]);
+    }
+
+    #[test]
+    fn claims_each_issue_once() {
+        let opened = todo("Fix it", "a.rs", &["x"]);
+        let existing = [remote(1, "Fix it", Some(&opened))];
+        let mut todos = [
+            todo("Fix it", "a.rs", &["x"]),
+            todo("Fix it", "a.rs", &["x"]),
+            todo("Fix it", "b.rs", &["y"]),
+        ];
+        assert_eq!(match_all(&mut todos, &existing), [1, 0, 0]);
+
+        // Issues referenced with `TODO(#1)` are claimed by the reference.
+        let mut todos =
+            [todo("Fix it", "a.rs", &["x"]), todo("Other", "b.rs", &[])];
+        todos[1].set_existing(1);
+        assert_eq!(match_all(&mut todos, &existing), [0, 1]);
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+
+use reqwest::header::AUTHORIZATION;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::command;
+use super::issue;
+use super::tracker;
+
+use command::Remote;
+use issue::Issue;
+use tracker::IssueTracker;
+
+// Gitea requires a color for new labels, so use GitHub's default one.
+const LABEL_COLOR: &str = "#ededed";
+
+pub struct Gitea {
+    client: reqwest::Client,
+    url: String,
+    labels_url: String,
+    milestones_url: String,
+    remote_url: String,
+    auth_header: String,
+    per_page: usize,
+    labels: RefCell<Option<Vec<Label>>>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Label {
+    id: usize,
+    name: String,
+}
+
+impl Gitea {
+    pub fn new(
+        token: String,
+        remote: &Remote,
+        api_url: Option<&str>,
+        per_page: usize,
+    ) -> Gitea {
+        //! Creates a client for the issues of a Gitea or Forgejo repository.
+        //!
+        //! The API endpoint is derived from the remote's host unless one
+        //! is given.
+        let api_url = match api_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("https://{}/api/v1", remote.host),
+        };
+        let repo_url = format!("{}/repos/{}", api_url, remote.path);
+
+        Gitea {
+            client: reqwest::Client::new(),
+            url: format!("{}/issues", repo_url),
+            labels_url: format!("{}/labels", repo_url),
+            milestones_url: format!("{}/milestones", repo_url),
+            remote_url: format!("https://{}/{}", remote.host, remote.path),
+            auth_header: format!("token {}", token),
+            per_page,
+            labels: RefCell::new(None),
+        }
+    }
+
+    fn get_label_ids(&self, names: &[String]) -> Option<Vec<usize>> {
+        //! Resolves label names to the IDs Gitea expects when creating an
+        //! issue, creating any label missing from the repository.
+        //!
+        //! The repository's labels are fetched once and cached.
+        if self.labels.borrow().is_none() {
+            let params = json!({ "limit": self.per_page });
+            let labels: Vec<Label> =
+                tracker::get_all_pages(&self.labels_url, &params, |url| {
+                    self.client
+                        .get(url)
+                        .header(AUTHORIZATION, self.auth_header.clone())
+                })?;
+            *self.labels.borrow_mut() = Some(labels);
+        }
+
+        let mut ids = Vec::new();
+        for name in names {
+            let existing = self
+                .labels
+                .borrow()
+                .iter()
+                .flatten()
+                .find(|label| &label.name == name)
+                .map(|label| label.id);
+            let id = match existing {
+                Some(id) => id,
+                None => self.create_label(name)?,
+            };
+            ids.push(id);
+        }
+
+        Some(ids)
+    }
+
+    fn create_label(&self, name: &str) -> Option<usize> {
+        //! Makes a POST request to create a label in the repository.
+        //!
+        //! Returns the ID of the new label.
+        let builder = self
+            .client
+            .post(&self.labels_url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "name": name, "color": LABEL_COLOR }));
+        let mut response =
+            tracker::send_successfully(builder, &self.labels_url)?;
+
+        let label = response.json::<Label>().ok()?;
+        if let Some(labels) = self.labels.borrow_mut().as_mut() {
+            labels.push(label.clone());
+        }
+
+        Some(label.id)
+    }
+
+    fn get_milestone_id(&self, title: &str) -> Option<usize> {
+        //! Looks up the ID of the milestone with the title, open or closed.
+        let params = json!({
+            "name": title,
+            "state": "all",
+            "limit": self.per_page,
+        });
+        let milestone = tracker::find_milestone(
+            &self.milestones_url,
+            &params,
+            |url| {
+                self.client
+                    .get(url)
+                    .header(AUTHORIZATION, self.auth_header.clone())
+            },
+            title,
+        )?;
+
+        Some(milestone.id)
+    }
+}
+
+impl IssueTracker for Gitea {
+    fn get_remote_url(&self) -> &str {
+        &self.remote_url
+    }
+
+    fn get_blob_url(&self, commit: &str) -> Option<String> {
+        Some(format!("{}/src/commit/{}", self.remote_url, commit))
+    }
+
+    fn get_issues_with_label(
+        &self,
+        label: &str,
+    ) -> Option<Vec<issue::Response>> {
+        //! Makes GET requests to retrieve every page of issues with the
+        //! label, leaving out pull requests.
+        let params = json!({
+            "labels": label,
+            "state": "all",
+            "type": "issues",
+            "limit": self.per_page,
+        });
+
+        tracker::get_all_pages(&self.url, &params, |url| {
+            self.client
+                .get(url)
+                .header(AUTHORIZATION, self.auth_header.clone())
+        })
+    }
+
+    fn open_issue(&self, issue: &Issue) -> Option<usize> {
+        //! Makes a POST request to create a new issue, with its labels and
+        //! milestone referenced by ID.
+        //!
+        //! Returns the issue's number within the repository.
+        let label_ids = self.get_label_ids(issue.get_labels())?;
+        let mut json = json!({
+            "title": issue.get_title(),
+            "body": issue.get_full_body(),
+            "labels": label_ids,
+            "assignees": issue.get_assignees(),
+        });
+        if let Some(milestone) = issue.get_milestone() {
+            json["milestone"] = json!(self.get_milestone_id(milestone)?);
+        }
+        let builder = self
+            .client
+            .post(&self.url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json);
+        let mut response = tracker::send_successfully(builder, &self.url)?;
+
+        match response.json::<issue::Response>() {
+            Ok(json) => Some(json.get_number()),
+            Err(_) => None,
+        }
+    }
+
+    fn update_issue(&self, number: usize, body: &str) -> bool {
+        //! Makes a PATCH request to replace the body of an issue.
+        //!
+        //! Returns a bool indicating whether the request was successful.
+        let url = format!("{}/{}", self.url, number);
+        let builder = self
+            .client
+            .patch(&url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "body": body }));
+
+        tracker::send_successfully(builder, &url).is_some()
+    }
+
+    fn close_issue(&self, number: usize, comment: &str) -> bool {
+        //! Makes a POST request to comment on an issue, followed by a PATCH
+        //! request to close it.
+        //!
+        //! Returns a bool indicating whether both requests were successful.
+        let url = format!("{}/{}", self.url, number);
+        let comments_url = format!("{}/comments", url);
+        let builder = self
+            .client
+            .post(&comments_url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "body": comment }));
+        if tracker::send_successfully(builder, &comments_url).is_none() {
+            return false;
+        }
+
+        let builder = self
+            .client
+            .patch(&url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "state": "closed" }));
+
+        tracker::send_successfully(builder, &url).is_some()
+    }
+}
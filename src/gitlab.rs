@@ -0,0 +1,278 @@
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::cli;
+use super::command;
+use super::issue;
+use super::tracker;
+
+use command::Remote;
+use issue::Issue;
+use tracker::IssueTracker;
+
+const TOKEN_HEADER: &str = "PRIVATE-TOKEN";
+
+pub struct GitLab {
+    client: reqwest::Client,
+    url: String,
+    users_url: String,
+    milestones_url: String,
+    remote_url: String,
+    token: HeaderValue,
+    per_page: usize,
+}
+
+#[derive(Deserialize)]
+struct GitLabIssue {
+    iid: usize,
+    title: String,
+    description: Option<String>,
+    state: String,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: usize,
+}
+
+impl GitLab {
+    pub fn new(
+        token: String,
+        remote: &Remote,
+        api_url: Option<&str>,
+        per_page: usize,
+    ) -> Option<GitLab> {
+        //! Creates a client for the issues of a GitLab project, addressed by
+        //! its URL-encoded path which GitLab accepts wherever a project ID
+        //! is expected.
+        //!
+        //! The API endpoint is derived from the remote's host unless one
+        //! is given.
+        //!
+        //! Returns None and outputs an error if the token can't be sent in
+        //! a header.
+        let token = match HeaderValue::from_str(token.trim()) {
+            Ok(token) => token,
+            Err(_) => {
+                cli::print_error(
+                    "The access token contains characters that aren't \
+                     allowed in a header.",
+                );
+                return None;
+            }
+        };
+        let api_url = match api_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => get_api_url(&remote.host),
+        };
+        let project_id = remote.path.replace('/', "%2F");
+
+        Some(GitLab {
+            client: reqwest::Client::new(),
+            url: format!("{}/projects/{}/issues", api_url, project_id),
+            users_url: format!("{}/users", api_url),
+            milestones_url: format!(
+                "{}/projects/{}/milestones",
+                api_url, project_id
+            ),
+            remote_url: format!("https://{}/{}", remote.host, remote.path),
+            token,
+            per_page,
+        })
+    }
+
+    fn get_user_ids(&self, usernames: &[String]) -> Option<Vec<usize>> {
+        //! Looks up the IDs of the users, since GitLab assigns issues by
+        //! user ID rather than username.
+        //!
+        //! Returns None and outputs an error if a user doesn't exist.
+        let mut ids = Vec::new();
+        for username in usernames {
+            let builder = self
+                .client
+                .get(&self.users_url)
+                .header(TOKEN_HEADER, self.token.clone())
+                .query(&[("username", username)]);
+            let mut response =
+                tracker::send_successfully(builder, &self.users_url)?;
+
+            match response.json::<Vec<User>>().ok()?.first() {
+                Some(user) => ids.push(user.id),
+                None => {
+                    cli::print_error(&format!(
+                        "No GitLab user named \"{}\" found.",
+                        username
+                    ));
+                    return None;
+                }
+            }
+        }
+
+        Some(ids)
+    }
+
+    fn get_milestone_id(&self, title: &str) -> Option<usize> {
+        //! Looks up the ID of the project milestone with the title, open or
+        //! closed.
+        let params = json!({ "title": title, "per_page": self.per_page });
+        let milestone = tracker::find_milestone(
+            &self.milestones_url,
+            &params,
+            |url| {
+                self.client
+                    .get(url)
+                    .header(TOKEN_HEADER, self.token.clone())
+            },
+            title,
+        )?;
+
+        Some(milestone.id)
+    }
+}
+
+impl IssueTracker for GitLab {
+    fn get_remote_url(&self) -> &str {
+        &self.remote_url
+    }
+
+    fn get_blob_url(&self, commit: &str) -> Option<String> {
+        Some(format!("{}/-/blob/{}", self.remote_url, commit))
+    }
+
+    fn get_issues_with_label(
+        &self,
+        label: &str,
+    ) -> Option<Vec<issue::Response>> {
+        //! Makes GET requests to retrieve every page of issues with the
+        //! label. GitLab numbers issues per project with their `iid`.
+        let params = json!({
+            "labels": label,
+            "state": "all",
+            "per_page": self.per_page,
+        });
+        let issues: Vec<GitLabIssue> =
+            tracker::get_all_pages(&self.url, &params, |url| {
+                self.client
+                    .get(url)
+                    .header(TOKEN_HEADER, self.token.clone())
+            })?;
+
+        Some(
+            issues
+                .into_iter()
+                .map(|issue| {
+                    issue::Response::new(
+                        issue.title,
+                        issue.iid,
+                        issue.description,
+                        issue.state == "opened",
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn open_issue(&self, issue: &Issue) -> Option<usize> {
+        //! Makes a POST request to create a new issue. GitLab takes labels
+        //! as a single comma separated string.
+        //!
+        //! Returns the issue's `iid`, its number within the project.
+        let assignee_ids = self.get_user_ids(issue.get_assignees())?;
+        let mut json = json!({
+            "title": issue.get_title(),
+            "description": issue.get_full_body(),
+            "labels": issue.get_labels().join(","),
+            "assignee_ids": assignee_ids,
+        });
+        if let Some(milestone) = issue.get_milestone() {
+            json["milestone_id"] = json!(self.get_milestone_id(milestone)?);
+        }
+        let builder = self
+            .client
+            .post(&self.url)
+            .header(TOKEN_HEADER, self.token.clone())
+            .json(&json);
+        let mut response = tracker::send_successfully(builder, &self.url)?;
+
+        match response.json::<GitLabIssue>() {
+            Ok(json) => Some(json.iid),
+            Err(_) => None,
+        }
+    }
+
+    fn update_issue(&self, number: usize, body: &str) -> bool {
+        //! Makes a PUT request to replace the description of an issue.
+        //!
+        //! Returns a bool indicating whether the request was successful.
+        let url = format!("{}/{}", self.url, number);
+        let builder = self
+            .client
+            .put(&url)
+            .header(TOKEN_HEADER, self.token.clone())
+            .json(&json!({ "description": body }));
+
+        tracker::send_successfully(builder, &url).is_some()
+    }
+
+    fn close_issue(&self, number: usize, comment: &str) -> bool {
+        //! Makes a POST request to add a note to an issue, followed by a PUT
+        //! request to close it.
+        //!
+        //! Returns a bool indicating whether both requests were successful.
+        let url = format!("{}/{}", self.url, number);
+        let notes_url = format!("{}/notes", url);
+        let builder = self
+            .client
+            .post(&notes_url)
+            .header(TOKEN_HEADER, self.token.clone())
+            .json(&json!({ "body": comment }));
+        if tracker::send_successfully(builder, &notes_url).is_none() {
+            return false;
+        }
+
+        let builder = self
+            .client
+            .put(&url)
+            .header(TOKEN_HEADER, self.token.clone())
+            .json(&json!({ "state_event": "close" }));
+
+        tracker::send_successfully(builder, &url).is_some()
+    }
+}
+
+fn get_api_url(host: &str) -> String {
+    //! Returns the REST API endpoint of a GitLab host, which self-hosted
+    //! instances serve under the same path as gitlab.com.
+    format!("https://{}/api/v4", host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new(token: &str) -> Option<GitLab> {
+        let remote = Remote {
+            host: "gitlab.com".to_string(),
+            path: "group/project".to_string(),
+        };
+        GitLab::new(token.to_string(), &remote, None, 100)
+    }
+
+    #[test]
+    fn trims_the_token() {
+        let gitlab = new(" glpat-token\n").unwrap();
+
+        assert_eq!(gitlab.token, "glpat-token");
+        assert_eq!(
+            gitlab.url,
+            "https://gitlab.com/api/v4/projects/group%2Fproject/issues"
+        );
+    }
+
+    #[test]
+    fn rejects_tokens_that_cant_be_headers() {
+        assert!(new("glpat\ntoken").is_none());
+        assert!(new("glpat\u{7f}token").is_none());
+    }
+}
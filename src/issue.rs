@@ -1,37 +1,112 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use console::style;
 use serde::Deserialize;
 use serde_json::{json, value::Value};
 
-pub const LABEL: &str = "TODO";
+use super::fingerprint::Fingerprint;
+use super::keyword::Keyword;
+
 const TITLE_PREFIX: &str = "Title:";
 const BODY_PREFIX: &str = "Body:";
+const LABELS_PREFIX: &str = "Labels:";
+const ASSIGNEES_PREFIX: &str = "Assignees:";
+const MILESTONE_PREFIX: &str = "Milestone:";
+const PRIORITY_LABEL_PREFIX: &str = "priority:";
+const SHORT_COMMIT_LENGTH: usize = 7;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const NOTES_MARKER: &str =
+    "<!-- Notes below this line are kept when todo-issue updates the issue. -->";
+
+#[derive(Clone, Default)]
+pub struct Annotations {
+    pub assignees: Vec<String>,
+    pub reference: Option<usize>,
+    pub priority: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: usize,
+}
+
+#[derive(Clone)]
+pub struct Blame {
+    pub commit: String,
+    pub author: String,
+    pub email: String,
+    // The author date, in seconds since the Unix epoch.
+    pub time: i64,
+}
 
 pub struct Issue {
     title: String,
     body: String,
+    keyword: String,
+    location: Location,
+    labels: Vec<String>,
+    annotations: Annotations,
+    milestone: Option<String>,
+    fingerprint: Fingerprint,
+    blame: Option<Blame>,
+    existing: Option<usize>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Response {
     title: String,
     number: usize,
+    body: Option<String>,
+    #[serde(default)]
+    state: String,
 }
 
 impl Issue {
-    pub fn new(title: String, body: String) -> Issue {
+    pub fn new(
+        title: String,
+        body: String,
+        keyword: &Keyword,
+        location: Location,
+        annotations: Annotations,
+        fingerprint: Fingerprint,
+    ) -> Issue {
+        //! Creates an issue labeled with the keyword's label, plus a
+        //! priority label if the todo was annotated with one.
+        let mut labels = vec![keyword.get_label().to_string()];
+        if let Some(priority) = &annotations.priority {
+            labels.push(format!("{}{}", PRIORITY_LABEL_PREFIX, priority));
+        }
+
         Issue {
-            title: title,
-            body: body,
+            title,
+            body,
+            keyword: keyword.get_name().to_string(),
+            location,
+            labels,
+            annotations,
+            milestone: None,
+            fingerprint,
+            blame: None,
+            existing: None,
         }
     }
 
-    pub fn from_string(string: String) -> Option<Issue> {
-        //! Initializes an Issue from a string with the following format:
+    pub fn edit(&self, string: String) -> Option<Issue> {
+        //! Initializes an Issue with the same keyword, location, annotations
+        //! and fingerprint as this one
+        //! from a string with the following format:
         //!
         //! 1. The first line starts with "Title:" followed by a nonempty string
-        //! 2. The second line starts with "Body:" followed by a description
-        //! that spans one or more lines.
-        
+        //! 2. Optional lines starting with "Labels:", "Assignees:" or
+        //!    "Milestone:", with labels and assignees separated by commas
+        //! 3. The next line starts with "Body:" followed by a description
+        //!    that spans one or more lines.
+        //!
+        //! The keyword's label is always kept, since issues are found by it.
+
         // 1. Split the string based on \n
         let split: Vec<&str> = string.split("\n").collect();
         // 2. Assert there are AT LEAST two parts
@@ -39,70 +114,256 @@ impl Issue {
             return None;
         }
 
-        let title_line = split[0];
-        let body_line = split[1];
-
         // 3. Assert first line starts with "Title:"
-        // 4. Assert second line starts with "Body:"
-        if !title_line.starts_with(TITLE_PREFIX)
-            || !body_line.starts_with(BODY_PREFIX)
-        {
-            return None;
-        }
+        let title = split[0].strip_prefix(TITLE_PREFIX)?.trim().to_string();
 
-        // 5. Strip prefixes and trim lines
-        let title = title_line[TITLE_PREFIX.len()..].trim().to_string();
-        let mut body = body_line[BODY_PREFIX.len()..].trim().to_string();
-
-        // 6. Ensure title is not empty.
+        // 4. Ensure title is not empty.
         if title.is_empty() {
             return None;
         }
 
-        // 7. Construct body from the rest of the file
-        for index in 2..split.len() {
+        // 5. Read the metadata lines up to the one starting with "Body:"
+        let mut labels = self.labels.clone();
+        let mut annotations = self.annotations.clone();
+        let mut milestone = self.milestone.clone();
+        let mut index = 1;
+        let mut body = loop {
+            let line = split.get(index)?;
+            index += 1;
+            if let Some(body) = line.strip_prefix(BODY_PREFIX) {
+                break body.trim().to_string();
+            } else if let Some(value) = line.strip_prefix(LABELS_PREFIX) {
+                labels = split_list(value);
+            } else if let Some(value) = line.strip_prefix(ASSIGNEES_PREFIX) {
+                annotations.assignees = split_list(value);
+            } else if let Some(value) = line.strip_prefix(MILESTONE_PREFIX) {
+                milestone = Some(value.trim().to_string())
+                    .filter(|milestone| !milestone.is_empty());
+            } else {
+                return None;
+            }
+        };
+        if let Some(label) = self.labels.first() {
+            if !labels.contains(label) {
+                labels.insert(0, label.clone());
+            }
+        }
+
+        // 6. Construct body from the rest of the file
+        for line in &split[index..] {
             body += "\n";
-            body += split[index];
+            body += line;
         }
 
-        Some(Issue { title, body })
+        Some(Issue {
+            title,
+            body,
+            keyword: self.keyword.clone(),
+            location: self.location.clone(),
+            labels,
+            annotations,
+            milestone,
+            fingerprint: self.fingerprint,
+            blame: self.blame.clone(),
+            existing: self.existing,
+        })
     }
 
     pub fn get_title(&self) -> String {
         self.title.clone()
     }
 
-    pub fn to_string(&self) -> String {
-        format!(
-            "{} {}\n{} {}",
-            TITLE_PREFIX, &self.title, BODY_PREFIX, &self.body
-        )
-        .to_string()
+    pub fn get_body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn get_keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn get_location(&self) -> &Location {
+        &self.location
+    }
+
+    pub fn get_annotations(&self) -> &Annotations {
+        &self.annotations
+    }
+
+    pub fn get_fingerprint(&self) -> &Fingerprint {
+        &self.fingerprint
+    }
+
+    pub fn get_labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn get_assignees(&self) -> &[String] {
+        &self.annotations.assignees
+    }
+
+    pub fn get_blame(&self) -> Option<&Blame> {
+        self.blame.as_ref()
+    }
+
+    pub fn set_blame(&mut self, blame: Blame) {
+        self.blame = Some(blame);
+    }
+
+    pub fn get_age(&self) -> u64 {
+        //! Returns the number of days since the todo was committed, or 0 if
+        //! it isn't committed yet.
+        self.blame.as_ref().map_or(0, |blame| blame.get_age())
+    }
+
+    pub fn get_milestone(&self) -> Option<&str> {
+        self.milestone.as_deref()
+    }
+
+    pub fn set_milestone(&mut self, milestone: &str) {
+        self.milestone = Some(milestone.to_string());
+    }
+
+    pub fn get_existing(&self) -> Option<usize> {
+        self.existing
+    }
+
+    pub fn set_existing(&mut self, number: usize) {
+        //! Marks the issue as already opened in the remote repo.
+        self.existing = Some(number);
+    }
+
+    pub fn add_label(&mut self, label: &str) {
+        if !self.labels.iter().any(|l| l == label) {
+            self.labels.push(label.to_string());
+        }
+    }
+
+    pub fn add_assignee(&mut self, assignee: &str) {
+        let assignees = &mut self.annotations.assignees;
+        if !assignees.iter().any(|a| a == assignee) {
+            assignees.push(assignee.to_string());
+        }
     }
 
     pub fn to_formatted_string(&self) -> String {
-        format!(
+        let mut string = format!(
             "{} {}\n{} {}",
             style(TITLE_PREFIX).bold(),
             &self.title,
             style(BODY_PREFIX).bold(),
             &self.body
-        )
-        .to_string()
+        );
+        string += &format!(
+            "\n{} {}",
+            style(LABELS_PREFIX).bold(),
+            self.labels.join(", ")
+        );
+        if !self.annotations.assignees.is_empty() {
+            string += &format!(
+                "\n{} {}",
+                style(ASSIGNEES_PREFIX).bold(),
+                self.annotations.assignees.join(", ")
+            );
+        }
+        if let Some(milestone) = &self.milestone {
+            string +=
+                &format!("\n{} {}", style(MILESTONE_PREFIX).bold(), milestone);
+        }
+
+        string
+    }
+
+    pub fn get_full_body(&self) -> String {
+        //! Returns the body sent to the remote repo, which ends with the
+        //! hidden fingerprint and a marker after which people can add notes.
+        join_full_body(&self.body, self.fingerprint)
     }
 
     pub fn to_json(&self) -> Value {
         json!({
             "title": &self.title,
-            "body": &self.body,
-            "labels": [
-                LABEL,
-            ]
+            "body": self.get_full_body(),
+            "labels": &self.labels,
+            "assignees": &self.annotations.assignees,
         })
     }
 }
 
+impl Blame {
+    pub fn new(commit: String) -> Blame {
+        Blame {
+            commit,
+            author: String::new(),
+            email: String::new(),
+            time: 0,
+        }
+    }
+
+    pub fn is_committed(&self) -> bool {
+        //! Lines that aren't committed yet are blamed on an all zero sha.
+        !self.commit.chars().all(|c| c == '0')
+    }
+
+    pub fn get_short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(SHORT_COMMIT_LENGTH)]
+    }
+
+    pub fn get_date(&self) -> String {
+        //! Formats the author date as YYYY-MM-DD in UTC, converting days
+        //! since the epoch to a civil date with Howard Hinnant's algorithm.
+        let days = self.time.div_euclid(SECONDS_PER_DAY) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460
+            + day_of_era / 36_524
+            - day_of_era / 146_096)
+            / 365;
+        let day_of_year = day_of_era
+            - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    pub fn get_age(&self) -> u64 {
+        //! Returns the number of whole days since the author date.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+
+        ((now - self.time) / SECONDS_PER_DAY).max(0) as u64
+    }
+}
+
 impl Response {
+    pub fn new(
+        title: String,
+        number: usize,
+        body: Option<String>,
+        is_open: bool,
+    ) -> Response {
+        //! Creates a response from the issue of a tracker whose API doesn't
+        //! use GitHub's shape.
+        let state = match is_open {
+            true => "open",
+            false => "closed",
+        };
+
+        Response {
+            title,
+            number,
+            body,
+            state: state.to_string(),
+        }
+    }
+
     pub fn get_title(&self) -> String {
         self.title.clone()
     }
@@ -110,4 +371,167 @@ impl Response {
     pub fn get_number(&self) -> usize {
         self.number
     }
-}
\ No newline at end of file
+
+    pub fn get_fingerprint(&self) -> Option<Fingerprint> {
+        Fingerprint::from_body(self.body.as_deref()?)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == "open"
+    }
+
+    pub fn get_updated_body(&self, issue: &Issue) -> Option<String> {
+        //! Returns the remote issue's body with the part generated by
+        //! todo-issue replaced by the issue's current body, keeping any notes
+        //! added below the notes marker. Issues opened before the marker
+        //! existed keep everything after their fingerprint instead.
+        //!
+        //! Returns None if the body is already up to date or the remote issue
+        //! wasn't opened by todo-issue. Bodies that only differ in the commit
+        //! their permalinks are pinned to are up to date, so issues aren't
+        //! updated after every commit.
+        let body = self.body.as_deref()?;
+        let notes = match body.find(NOTES_MARKER) {
+            Some(index) => &body[index + NOTES_MARKER.len()..],
+            None => {
+                let marker = self.get_fingerprint()?.to_marker();
+                &body[body.find(&marker)? + marker.len()..]
+            }
+        };
+
+        let mut updated = issue.get_full_body();
+        let notes = notes.trim();
+        if !notes.is_empty() {
+            updated += notes;
+            updated += "\n";
+        }
+
+        let body = body.replace("\r\n", "\n");
+        match mask_commits(updated.trim()) == mask_commits(body.trim()) {
+            true => None,
+            false => Some(updated),
+        }
+    }
+}
+
+fn mask_commits(text: &str) -> String {
+    //! Replaces every full commit sha, i.e. a run of 40 or 64 hexadecimal
+    //! digits, with zeros.
+    let mut masked = String::new();
+    let mut run = String::new();
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+            continue;
+        }
+        match run.len() {
+            40 | 64 => masked += &"0".repeat(run.len()),
+            _ => masked += &run,
+        }
+        run.clear();
+        masked.push(c);
+    }
+    masked.pop();
+
+    masked
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        //! Formats the issue to be edited, see Issue::edit.
+        writeln!(f, "{} {}", TITLE_PREFIX, &self.title)?;
+        writeln!(f, "{} {}", LABELS_PREFIX, self.labels.join(", "))?;
+        writeln!(
+            f,
+            "{} {}",
+            ASSIGNEES_PREFIX,
+            self.annotations.assignees.join(", ")
+        )?;
+        writeln!(
+            f,
+            "{} {}",
+            MILESTONE_PREFIX,
+            self.milestone.as_deref().unwrap_or("")
+        )?;
+        write!(f, "{} {}", BODY_PREFIX, &self.body)
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    //! Splits a comma separated list, leaving out empty items.
+    value
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+pub fn join_full_body(body: &str, fingerprint: Fingerprint) -> String {
+    //! Appends the hidden fingerprint and the notes marker to a body, see
+    //! Issue::get_full_body.
+    format!(
+        "{}\n\n{}\n{}\n",
+        body,
+        fingerprint.to_marker(),
+        NOTES_MARKER
+    )
+}
+
+pub fn split_full_body(full_body: &str) -> Option<(&str, Fingerprint)> {
+    //! Splits a body made by join_full_body back into the body and its
+    //! fingerprint, leaving out any notes.
+    let fingerprint = Fingerprint::from_body(full_body)?;
+    let end = full_body.find(&fingerprint.to_marker())?;
+
+    Some((full_body[..end].trim_end(), fingerprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(time: i64) -> String {
+        let mut blame = Blame::new("a".repeat(40));
+        blame.time = time;
+        blame.get_date()
+    }
+
+    #[test]
+    fn get_date_converts_epoch_seconds() {
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(1_583_020_799), "2020-02-29");
+        assert_eq!(date(1_583_020_800), "2020-03-01");
+        assert_eq!(date(1_735_689_599), "2024-12-31");
+        assert_eq!(date(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn uncommitted_lines_are_blamed_on_zeros() {
+        assert!(Blame::new("a".repeat(40)).is_committed());
+        assert!(!Blame::new("0".repeat(40)).is_committed());
+    }
+
+    #[test]
+    fn mask_commits_replaces_full_shas() {
+        let sha1 = "0123456789abcdef0123456789abcdef01234567";
+        let sha256 = "ab".repeat(32);
+        let text = format!("blob/{}/a.rs#L1 blob/{}/b.rs", sha1, sha256);
+
+        assert_eq!(
+            mask_commits(&text),
+            format!(
+                "blob/{}/a.rs#L1 blob/{}/b.rs",
+                "0".repeat(40),
+                "0".repeat(64)
+            )
+        );
+        assert_eq!(
+            mask_commits("commit 0123abc, #L12"),
+            "commit 0123abc, #L12"
+        );
+        assert_eq!(mask_commits(&sha1[..39]), &sha1[..39]);
+        assert_eq!(mask_commits(sha1), "0".repeat(40));
+    }
+}
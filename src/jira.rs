@@ -0,0 +1,755 @@
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, value::Value};
+
+use super::cli;
+use super::config;
+use super::fingerprint;
+use super::issue;
+use super::tracker;
+
+use config::JiraConfig;
+use fingerprint::Fingerprint;
+use issue::Issue;
+use tracker::IssueTracker;
+
+const DONE_CATEGORY: &str = "done";
+// The issue property holding what todo-issue needs to find and update its
+// issues, since descriptions can't hide it like markdown does.
+const PROPERTY_KEY: &str = "todo-issue";
+const SEARCH_FIELDS: &str = "summary,description,status";
+
+pub struct Jira {
+    client: reqwest::Client,
+    url: String,
+    // Jira Cloud's search endpoint, which only exists in version 3.
+    search_url: String,
+    remote_url: String,
+    token: String,
+    project: String,
+    issue_type: String,
+    component: Option<String>,
+    per_page: usize,
+}
+
+#[derive(Deserialize)]
+struct SearchResults {
+    #[serde(default)]
+    total: usize,
+    issues: Vec<JiraIssue>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: Fields,
+    #[serde(default)]
+    properties: Properties,
+}
+
+#[derive(Deserialize)]
+struct Fields {
+    summary: String,
+    // A string in version 2 of the API, and a document in version 3.
+    description: Option<Value>,
+    status: Status,
+}
+
+#[derive(Default, Deserialize)]
+struct Properties {
+    #[serde(rename = "todo-issue")]
+    todo_issue: Option<Property>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Property {
+    fingerprint: String,
+    // The body the description was made from, to tell if it changed.
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    #[serde(rename = "statusCategory")]
+    category: StatusCategory,
+}
+
+#[derive(Deserialize)]
+struct StatusCategory {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct Created {
+    key: String,
+}
+
+#[derive(Deserialize)]
+struct User {
+    #[serde(rename = "accountId")]
+    account_id: Option<String>,
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Transitions {
+    transitions: Vec<Transition>,
+}
+
+#[derive(Deserialize)]
+struct Transition {
+    id: String,
+    to: Status,
+}
+
+impl Jira {
+    pub fn new(
+        token: String,
+        url: &str,
+        config: &JiraConfig,
+        per_page: usize,
+    ) -> Jira {
+        //! Creates a client for the issues of a Jira project, using version
+        //! 2 of the REST API whose descriptions are plain strings, except
+        //! for searching on Jira Cloud.
+        //!
+        //! Issues are numbered by the numeric part of their key, e.g. 42
+        //! for PROJ-42.
+        let url = url.trim_end_matches('/');
+
+        Jira {
+            client: reqwest::Client::new(),
+            url: format!("{}/rest/api/2", url),
+            search_url: format!("{}/rest/api/3/search/jql", url),
+            remote_url: format!("{}/browse/{}", url, config.project),
+            token,
+            project: config.project.clone(),
+            issue_type: config.get_issue_type().to_string(),
+            component: config.component.clone(),
+            per_page,
+        }
+    }
+
+    fn authorize(&self, builder: RequestBuilder) -> RequestBuilder {
+        //! Jira Cloud authenticates with an email and API token, given as
+        //! `email:token`, while Jira Server and Data Center take a personal
+        //! access token.
+        match self.token.split_once(':') {
+            Some((email, token)) => builder.basic_auth(email, Some(token)),
+            None => builder.bearer_auth(&self.token),
+        }
+    }
+
+    fn get_key(&self, number: usize) -> String {
+        format!("{}-{}", self.project, number)
+    }
+
+    fn get_number(key: &str) -> Option<usize> {
+        key.rsplit('-').next()?.parse().ok()
+    }
+
+    fn search(&self, jql: &str) -> Option<Vec<JiraIssue>> {
+        //! Makes GET requests to retrieve every page of issues matching the
+        //! JQL query. Jira Cloud pages with a token given by each response,
+        //! while Jira Server and Data Center, which lack Cloud's search
+        //! endpoint, page with `startAt` as Jira doesn't send `Link` headers.
+        let per_page = self.per_page.to_string();
+        let mut issues = Vec::new();
+        let mut next_page_token: Option<String> = None;
+        let mut page = 1;
+
+        loop {
+            let mut params = vec![
+                ("jql", jql.to_string()),
+                ("fields", SEARCH_FIELDS.to_string()),
+                ("properties", PROPERTY_KEY.to_string()),
+                ("maxResults", per_page.clone()),
+            ];
+            params.extend(next_page_token.map(|t| ("nextPageToken", t)));
+            let builder = self
+                .authorize(self.client.get(&self.search_url))
+                .query(&params);
+            let mut response = tracker::send(builder, &self.search_url)?;
+            if page == 1 && response.status() == StatusCode::NOT_FOUND {
+                return self.search_by_offset(jql);
+            }
+            if !tracker::is_successful_response(response.status()) {
+                return None;
+            }
+
+            let results = response.json::<SearchResults>().ok()?;
+            cli::print_dim(&format!("Fetched page {}", page));
+            issues.extend(results.issues);
+            next_page_token = results.next_page_token;
+            if next_page_token.is_none() {
+                break;
+            }
+            page += 1;
+        }
+
+        Some(issues)
+    }
+
+    fn search_by_offset(&self, jql: &str) -> Option<Vec<JiraIssue>> {
+        //! Makes GET requests to the search endpoint of version 2, paging
+        //! through the results with `startAt`.
+        let url = format!("{}/search", self.url);
+        let per_page = self.per_page.to_string();
+        let mut issues = Vec::new();
+        let mut start_at = 0;
+        let mut page = 1;
+
+        loop {
+            let builder = self.authorize(self.client.get(&url)).query(&[
+                ("jql", jql),
+                ("fields", SEARCH_FIELDS),
+                ("properties", PROPERTY_KEY),
+                ("startAt", &start_at.to_string()),
+                ("maxResults", &per_page),
+            ]);
+            let mut response = tracker::send_successfully(builder, &url)?;
+
+            let results = response.json::<SearchResults>().ok()?;
+            let last = results.total.div_ceil(self.per_page);
+            cli::print_dim(&format!(
+                "Fetched page {} of {}",
+                page,
+                last.max(1)
+            ));
+            let is_done = results.issues.is_empty();
+            start_at += results.issues.len();
+            issues.extend(results.issues);
+            if is_done || start_at >= results.total {
+                break;
+            }
+            page += 1;
+        }
+
+        Some(issues)
+    }
+
+    fn get_account(&self, username: &str) -> Option<Value> {
+        //! Looks up a user to assign an issue to, by account ID on Jira
+        //! Cloud or by username on Jira Server.
+        let url = format!("{}/user/search", self.url);
+        let builder = self
+            .authorize(self.client.get(&url))
+            .query(&[("query", username), ("username", username)]);
+        let mut response = tracker::send_successfully(builder, &url)?;
+
+        let users = response.json::<Vec<User>>().ok()?;
+        match users.first() {
+            Some(User {
+                account_id: Some(id),
+                ..
+            }) => Some(json!({ "accountId": id })),
+            Some(User {
+                name: Some(name), ..
+            }) => Some(json!({ "name": name })),
+            _ => {
+                cli::print_error(&format!(
+                    "No Jira user named \"{}\" found.",
+                    username
+                ));
+                None
+            }
+        }
+    }
+}
+
+impl IssueTracker for Jira {
+    fn get_remote_url(&self) -> &str {
+        &self.remote_url
+    }
+
+    fn format_number(&self, number: usize) -> String {
+        self.get_key(number)
+    }
+
+    fn get_issues_with_label(
+        &self,
+        label: &str,
+    ) -> Option<Vec<issue::Response>> {
+        //! Searches the project for issues with the label using JQL.
+        //!
+        //! Issues opened by todo-issue are given the body they were made
+        //! from and their fingerprint, like the issues of other trackers.
+        //! Issues opened before their fingerprint was kept in a property
+        //! carry it in their description instead.
+        let jql = format!(
+            "project = \"{}\" AND labels = \"{}\" ORDER BY created ASC",
+            escape(&self.project),
+            escape(&to_label(label))
+        );
+
+        Some(
+            self.search(&jql)?
+                .into_iter()
+                .filter_map(|result| {
+                    let number = Self::get_number(&result.key)?;
+                    let body = match result.properties.todo_issue {
+                        Some(property) => Fingerprint::parse(
+                            &property.fingerprint,
+                        )
+                        .map(|fingerprint| {
+                            issue::join_full_body(&property.body, fingerprint)
+                        }),
+                        None => result
+                            .fields
+                            .description
+                            .and_then(|d| d.as_str().map(|d| d.to_string())),
+                    };
+
+                    Some(issue::Response::new(
+                        result.fields.summary,
+                        number,
+                        body,
+                        result.fields.status.category.key != DONE_CATEGORY,
+                    ))
+                })
+                .collect(),
+        )
+    }
+
+    fn open_issue(&self, issue: &Issue) -> Option<usize> {
+        //! Makes a POST request to create an issue of the configured type
+        //! in the project. Labels can't contain spaces in Jira, so they are
+        //! replaced with dashes, and only the first assignee is used since
+        //! Jira issues have a single assignee. Milestones are the project's
+        //! versions, set as the issue's fix version. The fingerprint is kept
+        //! in an issue property rather than the description.
+        //!
+        //! Returns the numeric part of the new issue's key.
+        let labels: Vec<String> = issue
+            .get_labels()
+            .iter()
+            .map(|label| to_label(label))
+            .collect();
+        let mut fields = json!({
+            "project": { "key": &self.project },
+            "issuetype": { "name": &self.issue_type },
+            "summary": issue.get_title(),
+            "description": to_wiki_markup(issue.get_body()),
+            "labels": labels,
+        });
+        if let Some(component) = &self.component {
+            fields["components"] = json!([{ "name": component }]);
+        }
+        if let Some(assignee) = issue.get_assignees().first() {
+            fields["assignee"] = self.get_account(assignee)?;
+        }
+        if let Some(milestone) = issue.get_milestone() {
+            fields["fixVersions"] = json!([{ "name": milestone }]);
+        }
+
+        let url = format!("{}/issue", self.url);
+        let builder = self.authorize(self.client.post(&url)).json(&json!({
+            "fields": fields,
+            "properties": [{
+                "key": PROPERTY_KEY,
+                "value": Property {
+                    fingerprint: issue.get_fingerprint().to_string(),
+                    body: issue.get_body().to_string(),
+                },
+            }],
+        }));
+        let mut response = tracker::send_successfully(builder, &url)?;
+
+        match response.json::<Created>() {
+            Ok(json) => Self::get_number(&json.key),
+            Err(_) => None,
+        }
+    }
+
+    fn update_issue(&self, number: usize, body: &str) -> bool {
+        //! Makes a PUT request to replace the description of an issue,
+        //! followed by a PUT request to store its new fingerprint.
+        //!
+        //! Returns a bool indicating whether both requests were successful.
+        let (body, fingerprint) = match issue::split_full_body(body) {
+            Some(split) => split,
+            None => return false,
+        };
+        let url = format!("{}/issue/{}", self.url, self.get_key(number));
+        let builder = self.authorize(self.client.put(&url)).json(&json!({
+            "fields": { "description": to_wiki_markup(body) },
+        }));
+        if tracker::send_successfully(builder, &url).is_none() {
+            return false;
+        }
+
+        let property_url = format!("{}/properties/{}", url, PROPERTY_KEY);
+        let builder =
+            self.authorize(self.client.put(&property_url))
+                .json(&Property {
+                    fingerprint: fingerprint.to_string(),
+                    body: body.to_string(),
+                });
+
+        tracker::send_successfully(builder, &property_url).is_some()
+    }
+
+    fn close_issue(&self, number: usize, comment: &str) -> bool {
+        //! Makes a POST request to comment on an issue, followed by a POST
+        //! request to transition it to the first status in the done
+        //! category, since Jira workflows name their statuses freely.
+        //!
+        //! Returns a bool indicating whether every request was successful.
+        let url = format!("{}/issue/{}", self.url, self.get_key(number));
+        let comment_url = format!("{}/comment", url);
+        let builder = self
+            .authorize(self.client.post(&comment_url))
+            .json(&json!({ "body": comment }));
+        if tracker::send_successfully(builder, &comment_url).is_none() {
+            return false;
+        }
+
+        let transitions_url = format!("{}/transitions", url);
+        let builder = self.authorize(self.client.get(&transitions_url));
+        let mut response =
+            match tracker::send_successfully(builder, &transitions_url) {
+                Some(response) => response,
+                None => return false,
+            };
+        let transition = response.json::<Transitions>().ok().and_then(|t| {
+            t.transitions
+                .into_iter()
+                .find(|t| t.to.category.key == DONE_CATEGORY)
+        });
+        let transition = match transition {
+            Some(transition) => transition,
+            None => {
+                cli::print_error(&format!(
+                    "No transition to a done status found for {}.",
+                    self.get_key(number)
+                ));
+                return false;
+            }
+        };
+
+        let builder = self
+            .authorize(self.client.post(&transitions_url))
+            .json(&json!({ "transition": { "id": transition.id } }));
+
+        tracker::send_successfully(builder, &transitions_url).is_some()
+    }
+}
+
+fn to_label(label: &str) -> String {
+    //! Replaces whitespace, which Jira labels can't contain, with dashes.
+    label.split_whitespace().collect::<Vec<&str>>().join("-")
+}
+
+fn to_wiki_markup(body: &str) -> String {
+    //! Converts the markdown of a body to Jira's wiki markup: fenced code
+    //! blocks become {noformat} blocks, which unlike {code} work for every
+    //! language, and inline code becomes {{monospace}}.
+    let mut lines = Vec::new();
+    // The length of the fence of the code block the line is in, if any.
+    let mut fence: Option<usize> = None;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        let length = trimmed.chars().take_while(|c| *c == '`').count();
+        match fence {
+            // Closing fences are at least as long as the opening one.
+            Some(open) if length >= open && length == trimmed.len() => {
+                fence = None;
+                lines.push("{noformat}".to_string());
+            }
+            Some(_) => lines.push(line.to_string()),
+            None if length >= 3 => {
+                fence = Some(length);
+                lines.push("{noformat}".to_string());
+            }
+            None => lines.push(to_monospace(line)),
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn to_monospace(line: &str) -> String {
+    //! Converts the inline code of a line, e.g. `code`, to {{code}}. Lines
+    //! with an unpaired backtick are left as they are.
+    if line.matches('`').count() % 2 == 1 {
+        return line.to_string();
+    }
+
+    line.split('`')
+        .enumerate()
+        .map(|(index, part)| match (index % 2, part.is_empty()) {
+            (1, false) => format!("{{{{{}}}}}", part),
+            (1, true) => "``".to_string(),
+            _ => part.to_string(),
+        })
+        .collect()
+}
+
+fn escape(value: &str) -> String {
+    //! Escapes a value to be quoted in a JQL query.
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::issue::{Annotations, Location};
+    use crate::keyword::Keyword;
+    use crate::mock::{Reply, Server};
+
+    fn jira(token: &str, server: &Server) -> Jira {
+        let config = JiraConfig {
+            url: None,
+            project: "PROJ".to_string(),
+            issue_type: Some("Bug".to_string()),
+            component: Some("Backend".to_string()),
+        };
+        Jira::new(token.to_string(), &server.url, &config, 2)
+    }
+
+    fn todo(title: &str) -> Issue {
+        let location = Location {
+            file: "a.rs".to_string(),
+            line: 1,
+            column: 0,
+        };
+        let annotations = Annotations {
+            priority: Some("P1".to_string()),
+            ..Annotations::default()
+        };
+        let mut issue = Issue::new(
+            title.to_string(),
+            "Fix `it`".to_string(),
+            &Keyword::new("TODO"),
+            location,
+            annotations,
+            Fingerprint::new("TODO", title, "a.rs", &[]),
+        );
+        issue.add_label("needs review");
+        issue
+    }
+
+    fn search_result(
+        key: &str,
+        status: &str,
+        property: Option<&Issue>,
+    ) -> String {
+        let properties = match property {
+            Some(todo) => json!({
+                "todo-issue": {
+                    "fingerprint": todo.get_fingerprint().to_string(),
+                    "body": todo.get_body(),
+                },
+            }),
+            None => json!({}),
+        };
+        json!({
+            "key": key,
+            "fields": {
+                "summary": format!("Summary of {}", key),
+                "description": "Old description",
+                "status": { "statusCategory": { "key": status } },
+            },
+            "properties": properties,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn converts_fences_to_noformat() {
+        let body = "Fix `parse` here\n\n````rust\n```\nlet x = 1;\n````\nDone";
+
+        assert_eq!(
+            to_wiki_markup(body),
+            "Fix {{parse}} here\n\n{noformat}\n```\nlet x = 1;\n\
+             {noformat}\nDone"
+        );
+    }
+
+    #[test]
+    fn converts_paired_backticks_to_monospace() {
+        assert_eq!(to_monospace("`a` and `b`"), "{{a}} and {{b}}");
+        assert_eq!(to_monospace("empty `` code"), "empty `` code");
+        assert_eq!(to_monospace("unpaired ` tick"), "unpaired ` tick");
+    }
+
+    #[test]
+    fn escapes_jql_values() {
+        assert_eq!(escape(r#"say "hi" \o/"#), r#"say \"hi\" \\o/"#);
+    }
+
+    #[test]
+    fn searches_cloud_with_page_tokens() {
+        let opened = todo("Fix it");
+        let server = Server::start(vec![
+            Reply::json(&format!(
+                r#"{{"issues": [{}, {}], "nextPageToken": "next"}}"#,
+                search_result("PROJ-1", "new", Some(&opened)),
+                search_result("PROJ-2", "done", None),
+            )),
+            Reply::json(&format!(
+                r#"{{"issues": [{}], "isLast": true}}"#,
+                search_result("PROJ-3", "indeterminate", None),
+            )),
+        ]);
+
+        let issues = jira("me@example.com:secret", &server)
+            .get_issues_with_label("TODO")
+            .unwrap();
+        let numbers: Vec<usize> =
+            issues.iter().map(|issue| issue.get_number()).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert_eq!(
+            issues[0].get_fingerprint(),
+            Some(*opened.get_fingerprint())
+        );
+        assert!(issues[0].is_open() && !issues[1].is_open());
+        assert_eq!(issues[1].get_fingerprint(), None);
+
+        let requests = server.requests();
+        assert!(requests[0].path.starts_with(
+            "/rest/api/3/search/jql?jql=project+%3D+%22PROJ%22+AND+\
+             labels+%3D+%22TODO%22"
+        ));
+        assert!(requests[0].path.contains("&properties=todo-issue"));
+        assert!(requests[0].path.ends_with("&maxResults=2"));
+        assert!(requests[1]
+            .path
+            .ends_with("&maxResults=2&nextPageToken=next"));
+        assert!(requests[0]
+            .header("Authorization")
+            .unwrap()
+            .starts_with("Basic "));
+    }
+
+    #[test]
+    fn falls_back_to_paging_by_offset() {
+        let server = Server::start(vec![
+            Reply::status(404),
+            Reply::json(&format!(
+                r#"{{"total": 3, "issues": [{}, {}]}}"#,
+                search_result("PROJ-1", "new", None),
+                search_result("PROJ-2", "new", None),
+            )),
+            Reply::json(&format!(
+                r#"{{"total": 3, "issues": [{}]}}"#,
+                search_result("PROJ-3", "new", None),
+            )),
+        ]);
+
+        let issues =
+            jira("pat", &server).get_issues_with_label("TODO").unwrap();
+        assert_eq!(issues.len(), 3);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].path.starts_with("/rest/api/2/search?jql="));
+        assert!(requests[1].path.contains("&startAt=0&maxResults=2"));
+        assert!(requests[2].path.contains("&startAt=2&maxResults=2"));
+        assert_eq!(requests[1].header("Authorization"), Some("Bearer pat"));
+    }
+
+    #[test]
+    fn opens_issues_with_fields_and_property() {
+        let server = Server::start(vec![Reply::json(r#"{"key": "PROJ-7"}"#)]);
+        let issue = todo("Fix it");
+
+        assert_eq!(jira("pat", &server).open_issue(&issue), Some(7));
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/rest/api/2/issue");
+        let json = requests[0].json();
+        assert_eq!(
+            json["fields"],
+            json!({
+                "project": { "key": "PROJ" },
+                "issuetype": { "name": "Bug" },
+                "components": [{ "name": "Backend" }],
+                "summary": "Fix it",
+                "description": "Fix {{it}}",
+                "labels": ["TODO", "priority:P1", "needs-review"],
+            })
+        );
+        assert_eq!(
+            json["properties"],
+            json!([{
+                "key": "todo-issue",
+                "value": {
+                    "fingerprint": issue.get_fingerprint().to_string(),
+                    "body": "Fix `it`",
+                },
+            }])
+        );
+    }
+
+    #[test]
+    fn updates_description_and_property() {
+        let server =
+            Server::start(vec![Reply::status(204), Reply::status(200)]);
+        let issue = todo("Fix it");
+
+        assert!(jira("pat", &server).update_issue(5, &issue.get_full_body()));
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(requests[0].path, "/rest/api/2/issue/PROJ-5");
+        assert_eq!(
+            requests[0].json(),
+            json!({ "fields": { "description": "Fix {{it}}" } })
+        );
+        assert_eq!(
+            requests[1].path,
+            "/rest/api/2/issue/PROJ-5/properties/todo-issue"
+        );
+        assert_eq!(
+            requests[1].json()["fingerprint"],
+            issue.get_fingerprint().to_string()
+        );
+    }
+
+    #[test]
+    fn closes_issues_with_a_done_transition() {
+        let server = Server::start(vec![
+            Reply::json("{}"),
+            Reply::json(
+                r#"{"transitions": [
+                    {"id": "11", "to": {"statusCategory": {"key": "new"}}},
+                    {"id": "31", "to": {"statusCategory": {"key": "done"}}}
+                ]}"#,
+            ),
+            Reply::status(204),
+        ]);
+
+        assert!(jira("pat", &server).close_issue(4, "Removed."));
+
+        let requests = server.requests();
+        let paths: Vec<(&str, &str)> = requests
+            .iter()
+            .map(|r| (r.method.as_str(), r.path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("POST", "/rest/api/2/issue/PROJ-4/comment"),
+                ("GET", "/rest/api/2/issue/PROJ-4/transitions"),
+                ("POST", "/rest/api/2/issue/PROJ-4/transitions"),
+            ]
+        );
+        assert_eq!(requests[0].json(), json!({ "body": "Removed." }));
+        assert_eq!(requests[2].json(), json!({ "transition": { "id": "31" } }));
+    }
+
+    #[test]
+    fn refuses_to_close_without_a_done_transition() {
+        let server = Server::start(vec![
+            Reply::json("{}"),
+            Reply::json(r#"{"transitions": []}"#),
+        ]);
+
+        assert!(!jira("pat", &server).close_issue(4, "Removed."));
+        assert_eq!(server.requests().len(), 2);
+    }
+}
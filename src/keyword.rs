@@ -0,0 +1,122 @@
+use super::body;
+
+pub const DEFAULT_KEYWORD: &str = "TODO";
+
+// Labels for well known keywords. Any other keyword is labeled with its name.
+const DEFAULT_LABELS: &[(&str, &str)] = &[
+    ("TODO", "TODO"),
+    ("FIXME", "bug"),
+    ("BUG", "bug"),
+    ("OPTIMIZE", "performance"),
+];
+
+#[derive(Clone)]
+pub struct Keyword {
+    name: String,
+    label: String,
+    template: Option<String>,
+    title_template: Option<String>,
+    title_prefix: Option<String>,
+}
+
+impl Keyword {
+    pub fn new(name: &str) -> Keyword {
+        //! Creates a keyword with its default label and no body template.
+        let label = DEFAULT_LABELS
+            .iter()
+            .find(|(keyword, _)| *keyword == name)
+            .map(|(_, label)| label.to_string())
+            .unwrap_or_else(|| name.to_string());
+
+        Keyword {
+            name: name.to_string(),
+            label,
+            template: None,
+            title_template: None,
+            title_prefix: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    pub fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+
+    pub fn get_title_template(&self) -> Option<&str> {
+        self.title_template.as_deref()
+    }
+
+    pub fn get_title_prefix(&self) -> Option<&str> {
+        self.title_prefix.as_deref()
+    }
+
+    pub fn set_template(&mut self, template: String) {
+        self.template = Some(template);
+    }
+
+    pub fn set_title_template(&mut self, template: String) {
+        self.title_template = Some(template);
+    }
+
+    pub fn set_title_prefix(&mut self, prefix: String) {
+        self.title_prefix = Some(prefix);
+    }
+
+    pub fn check_templates(&self) -> Result<(), String> {
+        //! Checks that the keyword's templates only use known placeholders.
+        //!
+        //! Returns an error message naming the template otherwise.
+        let templates = [
+            ("body template", &self.template),
+            ("title template", &self.title_template),
+            ("title prefix", &self.title_prefix),
+        ];
+        for (kind, template) in templates.iter() {
+            if let Some(template) = template {
+                body::check_template(template).map_err(|e| {
+                    format!("Invalid {} for keyword {}: {}", kind, self.name, e)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn find_in(&self, text: &str) -> Option<usize> {
+        //! Returns the byte index of the first occurrence of the keyword
+        //! in the text as a whole word, so "BUG" does not match "DEBUG".
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
+        text.match_indices(self.name.as_str())
+            .find(|(index, _)| {
+                let before = text[..*index].chars().next_back();
+                let after = text[index + self.name.len()..].chars().next();
+                !before.is_some_and(is_word_char)
+                    && !after.is_some_and(is_word_char)
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+pub fn get_labels(keywords: &[Keyword]) -> Vec<String> {
+    //! Returns the distinct labels used by the keywords, in order.
+    let mut labels: Vec<String> = Vec::new();
+    for keyword in keywords {
+        if !labels.iter().any(|label| label == keyword.get_label()) {
+            labels.push(keyword.get_label().to_string());
+        }
+    }
+
+    labels
+}
@@ -0,0 +1,322 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::cli;
+use super::issue;
+use super::tracker;
+
+use issue::Issue;
+use tracker::IssueTracker;
+
+const FRONT_MATTER: &str = "---";
+const EXTENSION: &str = "md";
+const OPEN: &str = "open";
+const CLOSED: &str = "closed";
+
+pub struct Local {
+    dir: PathBuf,
+    display_dir: String,
+    // The id of the last issue opened during this run.
+    last_id: RefCell<Option<usize>>,
+}
+
+struct Entry {
+    id: usize,
+    title: String,
+    state: String,
+    labels: Vec<String>,
+    assignees: Vec<String>,
+    milestone: Option<String>,
+    body: String,
+}
+
+impl Local {
+    pub fn new(root: &str, dir: &str) -> Local {
+        //! Creates a store of issues as markdown files in a directory of
+        //! the repository, which is created when the first issue is opened.
+        Local {
+            dir: Path::new(root).join(dir),
+            display_dir: dir.to_string(),
+            last_id: RefCell::new(None),
+        }
+    }
+
+    fn get_path(&self, id: usize) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, EXTENSION))
+    }
+
+    fn read_all(&self) -> Option<Vec<Entry>> {
+        //! Reads every issue in the directory, ignoring files that aren't
+        //! issues.
+        //!
+        //! Returns None and outputs an error if the directory can't be read.
+        if !self.dir.exists() {
+            return Some(Vec::new());
+        }
+
+        let files = match fs::read_dir(&self.dir) {
+            Ok(files) => files,
+            Err(e) => {
+                cli::print_error(&format!(
+                    "Failed to read {}: {}",
+                    self.display_dir, e
+                ));
+                return None;
+            }
+        };
+
+        let mut entries: Vec<Entry> = files
+            .filter_map(|file| file.ok())
+            .map(|file| file.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .filter_map(|path| Entry::parse(&fs::read_to_string(path).ok()?))
+            .collect();
+        entries.sort_by_key(|entry| entry.id);
+
+        Some(entries)
+    }
+
+    fn read(&self, id: usize) -> Option<Entry> {
+        //! Reads the issue with the id, outputting an error if there's none.
+        let entry = fs::read_to_string(self.get_path(id))
+            .ok()
+            .and_then(|contents| Entry::parse(&contents));
+        if entry.is_none() {
+            cli::print_error(&format!(
+                "Issue #{} not found in {}.",
+                id, self.display_dir
+            ));
+        }
+
+        entry
+    }
+
+    fn write(&self, entry: &Entry) -> bool {
+        //! Writes the issue to its file, creating the directory if needed.
+        //!
+        //! Returns a bool indicating whether the file was written.
+        let result = fs::create_dir_all(&self.dir).and_then(|_| {
+            fs::write(self.get_path(entry.id), entry.to_file_contents())
+        });
+
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                cli::print_error(&format!(
+                    "Failed to write issue #{} to {}: {}",
+                    entry.id, self.display_dir, e
+                ));
+                false
+            }
+        }
+    }
+}
+
+impl IssueTracker for Local {
+    fn get_remote_url(&self) -> &str {
+        &self.display_dir
+    }
+
+    fn get_issues_with_label(
+        &self,
+        label: &str,
+    ) -> Option<Vec<issue::Response>> {
+        //! Returns every issue in the directory with the label.
+        let entries = self.read_all()?;
+
+        Some(
+            entries
+                .into_iter()
+                .filter(|entry| entry.labels.iter().any(|l| l == label))
+                .map(|entry| {
+                    issue::Response::new(
+                        entry.title,
+                        entry.id,
+                        Some(entry.body),
+                        entry.state == OPEN,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn open_issue(&self, issue: &Issue) -> Option<usize> {
+        //! Writes the issue to a new file, numbered one after the highest
+        //! id in the directory.
+        //!
+        //! Returns the id of the new issue.
+        let last_id = match *self.last_id.borrow() {
+            Some(id) => id,
+            None => self.read_all()?.last().map_or(0, |entry| entry.id),
+        };
+        let entry = Entry {
+            id: last_id + 1,
+            title: issue.get_title(),
+            state: OPEN.to_string(),
+            labels: issue.get_labels().to_vec(),
+            assignees: issue.get_assignees().to_vec(),
+            milestone: issue.get_milestone().map(|m| m.to_string()),
+            body: issue.get_full_body(),
+        };
+        if !self.write(&entry) {
+            return None;
+        }
+
+        *self.last_id.borrow_mut() = Some(entry.id);
+        Some(entry.id)
+    }
+
+    fn update_issue(&self, number: usize, body: &str) -> bool {
+        //! Replaces the body of an issue, keeping its front matter.
+        match self.read(number) {
+            Some(mut entry) => {
+                entry.body = body.to_string();
+                self.write(&entry)
+            }
+            None => false,
+        }
+    }
+
+    fn close_issue(&self, number: usize, comment: &str) -> bool {
+        //! Marks an issue as closed and appends the comment to its body,
+        //! below any notes.
+        match self.read(number) {
+            Some(mut entry) => {
+                entry.state = CLOSED.to_string();
+                entry.body =
+                    format!("{}\n{}\n", entry.body.trim_end(), comment);
+                self.write(&entry)
+            }
+            None => false,
+        }
+    }
+}
+
+impl Entry {
+    fn parse(contents: &str) -> Option<Entry> {
+        //! Parses a file of the following format, where the values are JSON
+        //! so titles can contain any character:
+        //!
+        //! ---
+        //! id: 1
+        //! title: "Title"
+        //! state: "open"
+        //! labels: ["TODO"]
+        //! assignees: []
+        //! milestone: "v1.0"
+        //! ---
+        //! Body
+        //!
+        //! The milestone line is only there for issues with a milestone.
+        let contents = contents.replace("\r\n", "\n");
+        let rest = contents.strip_prefix(FRONT_MATTER)?.strip_prefix('\n')?;
+        let end = rest.find(&format!("\n{}\n", FRONT_MATTER))?;
+        let front_matter = &rest[..end];
+        let body = &rest[end + FRONT_MATTER.len() + 2..];
+
+        let get = |key: &str| {
+            front_matter.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.strip_prefix(':')?;
+                Some(value.trim().to_string())
+            })
+        };
+
+        Some(Entry {
+            id: get("id")?.parse().ok()?,
+            title: from_json(&get("title")?)?,
+            state: from_json(&get("state")?)?,
+            labels: get("labels")
+                .and_then(|v| from_json(&v))
+                .unwrap_or_default(),
+            assignees: get("assignees")
+                .and_then(|v| from_json(&v))
+                .unwrap_or_default(),
+            milestone: get("milestone").and_then(|v| from_json(&v)),
+            body: body.to_string(),
+        })
+    }
+
+    fn to_file_contents(&self) -> String {
+        let milestone = match &self.milestone {
+            Some(milestone) => format!("milestone: {}\n", to_json(milestone)),
+            None => String::new(),
+        };
+
+        format!(
+            "{}\nid: {}\ntitle: {}\nstate: {}\nlabels: {}\nassignees: {}\n{}{}\n{}",
+            FRONT_MATTER,
+            self.id,
+            to_json(&self.title),
+            to_json(&self.state),
+            to_json(&self.labels),
+            to_json(&self.assignees),
+            milestone,
+            FRONT_MATTER,
+            self.body
+        )
+    }
+}
+
+fn from_json<T: DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_str(value).ok()
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_front_matter_and_body() {
+        let contents = "---\r\nid: 3\r\ntitle: \"Fix: \\\"it\\\"\"\r\n\
+                        state: \"open\"\r\nlabels: [\"TODO\", \"bug\"]\r\n\
+                        assignees: []\r\nmilestone: \"v1.0\"\r\n---\r\n\
+                        Body\r\n\r\nMore\r\n";
+        let entry = Entry::parse(contents).unwrap();
+
+        assert_eq!(entry.id, 3);
+        assert_eq!(entry.title, "Fix: \"it\"");
+        assert_eq!(entry.state, OPEN);
+        assert_eq!(entry.labels, ["TODO", "bug"]);
+        assert!(entry.assignees.is_empty());
+        assert_eq!(entry.milestone.as_deref(), Some("v1.0"));
+        assert_eq!(entry.body, "Body\n\nMore\n");
+    }
+
+    #[test]
+    fn round_trips_file_contents() {
+        let entry = Entry {
+            id: 12,
+            title: "Title --- with a dash".to_string(),
+            state: CLOSED.to_string(),
+            labels: vec!["TODO".to_string()],
+            assignees: vec!["octocat".to_string()],
+            milestone: None,
+            body: "---\nBody\n".to_string(),
+        };
+        let parsed = Entry::parse(&entry.to_file_contents()).unwrap();
+
+        assert_eq!(parsed.id, entry.id);
+        assert_eq!(parsed.title, entry.title);
+        assert_eq!(parsed.state, entry.state);
+        assert_eq!(parsed.labels, entry.labels);
+        assert_eq!(parsed.assignees, entry.assignees);
+        assert_eq!(parsed.milestone, None);
+        assert_eq!(parsed.body, entry.body);
+    }
+
+    #[test]
+    fn rejects_files_without_front_matter() {
+        assert!(Entry::parse("Body").is_none());
+        assert!(Entry::parse("---\nid: 1\n").is_none());
+        assert!(Entry::parse("---\nid: x\ntitle: \"\"\nstate: \"\"\n---\n")
+            .is_none());
+    }
+}
@@ -1,37 +1,172 @@
+mod body;
+mod check;
 mod cli;
 mod command;
+mod config;
+mod fingerprint;
+mod gitea;
+mod gitlab;
 mod issue;
+mod jira;
+mod keyword;
+mod local;
+#[cfg(test)]
+mod mock;
 mod parse;
+mod report;
 mod request;
+mod sync;
+mod syntax;
+mod token;
+mod tracker;
 
-use request::Request;
+use cli::{Format, Mode};
+use command::Remote;
 
 fn main() {
     let args = match cli::init() {
         Some(args) => args,
-        None => return,
+        None => std::process::exit(1),
     };
-    let remote = match command::get_remote_name(args.is_dry_run()) {
-        Some(remote) => remote,
-        None => return,
+    if let Mode::ShowConfig = args.get_mode() {
+        print!("{}", args.get_config().to_effective().to_toml());
+        return;
+    }
+    if let Mode::Check = args.get_mode() {
+        if !check::run(&args) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let is_sync = matches!(args.get_mode(), Mode::Sync);
+    // Syncing needs the remote's issues even on a dry run.
+    let is_offline = args.is_dry_run() && !is_sync;
+
+    let added = match args.get_since() {
+        Some(reference) => match command::get_added_lines(reference) {
+            Some(added) => Some(added),
+            None => std::process::exit(1),
+        },
+        None => None,
     };
 
-    let request = Request::new(args.get_token(), remote);
-    let issues = match request.get_issues(args.is_dry_run()) {
-        Some(issues) => issues,
-        None => return,
+    let config = args.get_config();
+    let remote = match tracker::uses_remote(config.get_tracker()) {
+        true => match command::get_remote(
+            config.get_remote(),
+            config.get_api_url(),
+            is_offline,
+        ) {
+            Some(remote) => remote,
+            None => std::process::exit(1),
+        },
+        false => Remote::default(),
     };
 
+    let name = config
+        .get_tracker()
+        .unwrap_or_else(|| tracker::detect(&remote.host));
+    let token = match is_offline || !tracker::uses_token(name) {
+        true => String::new(),
+        false => match token::find_token(
+            args.get_token(),
+            name,
+            &remote.host,
+            args.is_verbose(),
+        ) {
+            Some(token) => token,
+            None => std::process::exit(1),
+        },
+    };
+    let tracker = match tracker::new_tracker(
+        name,
+        token,
+        &remote,
+        config,
+        args.get_per_page(),
+    ) {
+        Some(tracker) => tracker,
+        None => std::process::exit(1),
+    };
+    let labels = keyword::get_labels(args.get_keywords());
+    let issues = match tracker.get_issues(&labels, is_offline) {
+        Some(issues) => issues,
+        None => std::process::exit(1),
+    };
 
-    let files = command::get_tracked_files();
-    let file_to_issues = parse::find_all_todos(
+    let mut files = command::get_tracked_files();
+    // Only files changed since the ref can contain new todos.
+    if let Some(added) = &added {
+        files.retain(|file| added.contains_key(file));
+    }
+    // Links to code need a remote to point at. Paths are relative to the
+    // current directory, which may be below the root of the repository.
+    let blob_url = match remote.host.is_empty() || remote.path.is_empty() {
+        true => None,
+        false => tracker
+            .get_blob_url(&command::get_head_commit())
+            .map(|url| {
+                let prefix = command::get_path_prefix();
+                format!("{}/{}", url, prefix)
+                    .trim_end_matches('/')
+                    .to_string()
+            }),
+    };
+    let mut found = match parse::find_all_todos(
         &files,
         &issues,
-        args.get_pattern(),
-        args.is_verbose(),
-    );
+        blob_url.as_deref(),
+        &args,
+    ) {
+        Some(found) => found,
+        None => std::process::exit(1),
+    };
+    if let Some(added) = &added {
+        parse::retain_added(&mut found, added);
+    }
+    if let Some(days) = args.get_older_than() {
+        parse::retain_older_than(&mut found, days);
+    }
+    if is_sync {
+        if !sync::close_removed_todos(&*tracker, &found, &issues, &args) {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    if file_to_issues.len() > 0 && !args.is_dry_run() {
-        cli::output_issues_and_prompt_user(&request, &file_to_issues);
+    if args.get_format() != Format::Text {
+        report::print_todos(&found, args.get_keywords(), args.get_format());
+    }
+    if !args.is_dry_run()
+        && !sync::update_moved_todos(
+            &*tracker,
+            &found,
+            &issues,
+            args.is_verbose(),
+        )
+    {
+        std::process::exit(1);
+    }
+    let file_to_issues = parse::get_new_issues(found, &args);
+
+    if file_to_issues.is_empty() || args.is_dry_run() {
+        return;
+    }
+    let is_successful = match args.is_batch() {
+        true => cli::open_all_issues(
+            &*tracker,
+            &file_to_issues,
+            args.get_max_issues(),
+            args.get_sort(),
+        ),
+        false => cli::output_issues_and_prompt_user(
+            &*tracker,
+            &file_to_issues,
+            args.get_sort(),
+        ),
+    };
+    if !is_successful {
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,177 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::value::Value;
+
+// How long to wait for a request before giving up on the client, so a test
+// making fewer requests than expected fails rather than hangs.
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Server {
+    pub url: String,
+    handle: JoinHandle<Vec<Request>>,
+}
+
+pub struct Reply {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+pub struct Request {
+    pub method: String,
+    // The path including the query string, e.g. `/issues?page=2`.
+    pub path: String,
+    headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Server {
+    pub fn start(replies: Vec<Reply>) -> Server {
+        //! Starts a server on a free local port that answers each request
+        //! with the next reply, closing the connection after every one.
+        //!
+        //! `{url}` in the headers and body of a reply is replaced with the
+        //! server's url, for links to further pages.
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        listener.set_nonblocking(true).unwrap();
+
+        let server_url = url.clone();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for reply in replies {
+                let mut stream = match accept(&listener) {
+                    Some(stream) => stream,
+                    None => break,
+                };
+                requests.push(read_request(&stream));
+                stream
+                    .write_all(reply.to_bytes(&server_url).as_slice())
+                    .unwrap();
+            }
+
+            requests
+        });
+
+        Server { url, handle }
+    }
+
+    pub fn requests(self) -> Vec<Request> {
+        //! Waits for every reply to be sent, and returns the requests that
+        //! were received in order.
+        self.handle.join().expect("Mock server panicked")
+    }
+}
+
+impl Reply {
+    pub fn json(body: &str) -> Reply {
+        Reply {
+            status: 200,
+            headers: vec![(
+                "Content-Type".to_string(),
+                "application/json".to_string(),
+            )],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(status: u16) -> Reply {
+        Reply {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Reply {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    fn to_bytes(&self, url: &str) -> Vec<u8> {
+        let body = self.body.replace("{url}", url);
+        let mut head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.status,
+            body.len()
+        );
+        for (name, value) in &self.headers {
+            head += &format!("{}: {}\r\n", name, value.replace("{url}", url));
+        }
+
+        format!("{}\r\n{}", head, body).into_bytes()
+    }
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("Request body isn't JSON")
+    }
+}
+
+fn accept(listener: &TcpListener) -> Option<TcpStream> {
+    let start = Instant::now();
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).unwrap();
+                return Some(stream);
+            }
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                if start.elapsed() > ACCEPT_TIMEOUT {
+                    return None;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => panic!("Failed to accept a connection: {}", e),
+        }
+    }
+}
+
+fn read_request(stream: &TcpStream) -> Request {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).unwrap();
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let length = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).unwrap();
+
+    Request {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    }
+}
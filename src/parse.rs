@@ -1,155 +1,768 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, prelude::*, BufReader};
+use std::ops::Range;
 use std::str;
 
+use super::body;
 use super::cli;
+use super::command;
+use super::fingerprint;
 use super::issue;
+use super::keyword;
+use super::syntax;
+use body::BodyBuilder;
+use cli::Args;
+use command::Change;
 use console::style;
-use issue::Issue;
+use fingerprint::Fingerprint;
+use issue::{Annotations, Blame, Issue, Location, Response};
+use keyword::Keyword;
+use syntax::{Comment, Scanner, Syntax};
 
-const TODO: &str = "TODO";
-
-pub fn find_all_todos(
-    files: &Vec<String>,
-    issues: &HashSet<String>,
-    pattern: &glob::Pattern,
-    is_verbose: bool,
+pub fn get_new_issues(
+    found: Vec<(String, Vec<Issue>)>,
+    args: &Args,
 ) -> HashMap<String, Vec<Issue>> {
-    //! Reads every file that matches the specified glob pattern
-    //! and searches for "todo" comments line by line.
+    //! Filters the todos found by find_all_todos, skipping the ones
+    //! already opened in the remote repo, and outputs how many are left
+    //! per file and per keyword.
     //!
     //! Returns a HashMap that maps file path to a vector of Issue objects that
     //! represents the "todos" found in the file.
     let mut file_to_issues = HashMap::new();
-    let mut total = 0;
+    let keywords = args.get_keywords();
+    let mut counts = vec![0; keywords.len()];
+    let names = get_names(keywords);
+
+    for (file, mut vector) in found {
+        if args.is_verbose() {
+            for issue in vector.iter().filter(|i| i.get_existing().is_some()) {
+                cli::print_warning(&format!(
+                    "\"{}\" in {} was already opened as issue #{}.",
+                    issue.get_title(),
+                    file,
+                    issue.get_existing().unwrap()
+                ));
+            }
+        }
+        vector.retain(|issue| issue.get_existing().is_none());
+
+        let mut file_counts = vec![0; keywords.len()];
+        for issue in &vector {
+            if let Some(index) = keywords
+                .iter()
+                .position(|k| k.get_name() == issue.get_keyword())
+            {
+                file_counts[index] += 1;
+                counts[index] += 1;
+            }
+        }
+        if !vector.is_empty() {
+            cli::print_info(&format!(
+                "Found {} in {}",
+                format_counts(keywords, &file_counts),
+                style(&file).italic()
+            ));
+            file_to_issues.insert(file, vector);
+        }
+    }
+
+    match counts.iter().sum() {
+        0 => cli::print_success(&format!(
+            "No {} comments found. You're all set!",
+            names
+        )),
+        total => {
+            cli::print_info(&format!(
+                "Found {} {} total.",
+                style(total).bold(),
+                cli::handle_plural(&total, "comment")
+            ));
+            for (keyword, count) in keywords.iter().zip(&counts) {
+                if *count > 0 {
+                    cli::print_info(&format!(
+                        "  {:>5} {}",
+                        style(count).bold(),
+                        keyword.get_name()
+                    ));
+                }
+            }
+        }
+    }
+
+    file_to_issues
+}
 
-    let pattern_str = pattern.as_str();
-    if pattern_str == "*" {
-        println!("Searching all files tracked by git for TODO comments...");
+pub fn find_all_todos(
+    files: &Vec<String>,
+    existing: &[Response],
+    blob_url: Option<&str>,
+    args: &Args,
+) -> Option<Vec<(String, Vec<Issue>)>> {
+    //! Reads every file that matches the include and exclude glob patterns
+    //! and searches for "todo" comments line by line. Files that aren't
+    //! text, like images, and files deleted from the working tree are
+    //! skipped.
+    //!
+    //! Issue bodies link to the todos in the files at blob_url, the web
+    //! url of the files in the current commit, if given.
+    //!
+    //! Returns every todo found per file, with the ones already opened in
+    //! the remote repo (or referencing an issue) marked as existing.
+    //! Returns None and outputs an error if any other file can't be read,
+    //! since its todos would otherwise be taken as removed.
+    let config = args.get_config();
+    let names = get_names(args.get_keywords());
+    if config.include.is_empty() {
+        cli::print_info(&format!(
+            "Searching all files tracked by git for {} comments...",
+            names
+        ));
     } else {
-        println!(
-            "Searching all files matching pattern \"{}\" for TODO comments...",
-            pattern_str
-        );
+        cli::print_info(&format!(
+            "Searching all files matching pattern \"{}\" for {} comments...",
+            config.include.join("\", \""),
+            names
+        ));
     }
 
+    let branch = command::get_branch_name();
+    // Links are pinned to the current commit, so they only point at the
+    // right line if nothing above it changed since.
+    let first_changed = match blob_url {
+        Some(_) => command::get_first_changed_lines(),
+        None => HashMap::new(),
+    };
+    let mut found = Vec::new();
     for file in files {
-        if pattern.matches(&file) {
-            let result = find_todos_in_file(&file, &issues, is_verbose);
-            if let Ok(vector) = result {
-                let num_issues = vector.len();
-                if num_issues > 0 {
-                    println!(
-                        "Found {} {} in {}",
-                        style(num_issues).bold(),
-                        cli::handle_plural(&num_issues, "TODO"),
-                        style(file).italic()
-                    );
-                    file_to_issues.insert(file.clone(), vector);
-                    total += num_issues;
-                }
+        if !args.is_included(file) {
+            continue;
+        }
+        let first_changed = first_changed.get(file).copied();
+        match find_todos_in_file(file, blob_url, first_changed, &branch, args) {
+            Ok(vector) => found.push((file.clone(), vector)),
+            Err(e)
+                if e.kind() == io::ErrorKind::InvalidData
+                    || e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => {
+                cli::print_error(&format!("Failed to read {}: {}", file, e));
+                return None;
             }
         }
     }
 
-    match total {
-        0 => cli::print_success("No TODOs found. You're all set!"),
-        num_issues => println!(
-            "Found {} {} total.",
-            style(num_issues).bold(),
-            cli::handle_plural(&num_issues, "TODO")
-        ),
+    let mut all_issues: Vec<&mut Issue> = found
+        .iter_mut()
+        .flat_map(|(_, vector)| vector.iter_mut())
+        .collect();
+    fingerprint::match_existing(&mut all_issues, existing);
+
+    Some(found)
+}
+
+pub fn find_all_todos_at(
+    reference: &str,
+    args: &Args,
+) -> Option<Vec<(String, Vec<Issue>)>> {
+    //! Searches the files that match the include and exclude glob patterns
+    //! as they were in a commit, reading them from git rather than from
+    //! the working tree.
+    //!
+    //! Returns None and outputs an error if the reference can't be resolved.
+    let files = match command::get_files_at(reference) {
+        Some(files) => files,
+        None => {
+            cli::print_error(&format!(
+                "Unknown git reference \"{}\".",
+                reference
+            ));
+            return None;
+        }
+    };
+
+    let mut found = Vec::new();
+    for file in files.iter().filter(|file| args.is_included(file)) {
+        if let Some(contents) = command::read_file_at(reference, file) {
+            let lines: Vec<String> =
+                contents.lines().map(|line| line.to_string()).collect();
+            let issues = find_todos_in_lines(file, &lines, args)
+                .into_iter()
+                .map(|todo| {
+                    create_issue(
+                        todo, file, &lines, None, None, reference, args,
+                    )
+                })
+                .collect();
+            found.push((file.clone(), issues));
+        }
     }
-    
-    file_to_issues
+
+    Some(found)
+}
+
+pub struct Removal {
+    pub commit: String,
+    pub fingerprint: Fingerprint,
+    pub title: String,
+}
+
+pub fn find_removed_todos(args: &Args) -> Vec<Removal> {
+    //! Searches the history of the current branch for commits that removed
+    //! a "todo" comment, newest first. Comments moved to another place in
+    //! the same commit weren't removed, so they are left out.
+    //!
+    //! Only the text half of the fingerprints returned is meaningful, since
+    //! the lines around a removed comment aren't known.
+    let keywords: Vec<String> = args
+        .get_keywords()
+        .iter()
+        .map(|keyword| escape_regex(keyword.get_name()))
+        .collect();
+    let changes = command::get_changes(&keywords.join("|"));
+
+    get_removals(&changes, args)
+}
+
+fn get_removals(changes: &[Change], args: &Args) -> Vec<Removal> {
+    //! Returns the todos removed by each commit of the changes, which are
+    //! grouped by commit, leaving out those added back in the same commit.
+    let mut removals: Vec<Removal> = Vec::new();
+    for commit in changes.chunk_by(|a, b| a.commit == b.commit) {
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for change in commit.iter().filter(|c| args.is_included(&c.path)) {
+            let path = &change.path;
+            for todo in find_todos_in_lines(path, &change.removed, args) {
+                let name = todo.keyword.get_name();
+                let fingerprint =
+                    Fingerprint::new(name, &todo.title, path, &[]);
+                removed.push((fingerprint, todo.title));
+            }
+            for todo in find_todos_in_lines(path, &change.added, args) {
+                let name = todo.keyword.get_name();
+                added.push(Fingerprint::new(name, &todo.title, path, &[]));
+            }
+        }
+
+        removals.extend(
+            removed
+                .into_iter()
+                .filter(|(fingerprint, _)| {
+                    !added.iter().any(|a| a.has_same_text(fingerprint))
+                })
+                .map(|(fingerprint, title)| Removal {
+                    commit: commit[0].commit.clone(),
+                    fingerprint,
+                    title,
+                }),
+        );
+    }
+
+    removals
+}
+
+pub fn retain_added(
+    found: &mut Vec<(String, Vec<Issue>)>,
+    added: &HashMap<String, Vec<Range<u32>>>,
+) {
+    //! Keeps only the todos whose line is among the lines added to their
+    //! file, as returned by command::get_added_lines.
+    for (file, issues) in found.iter_mut() {
+        let ranges = match added.get(file) {
+            Some(ranges) => ranges,
+            None => {
+                issues.clear();
+                continue;
+            }
+        };
+        issues.retain(|issue| {
+            let line = issue.get_location().line;
+            ranges.iter().any(|range| range.contains(&line))
+        });
+    }
+    found.retain(|(_, issues)| !issues.is_empty());
+}
+
+pub fn retain_older_than(found: &mut Vec<(String, Vec<Issue>)>, days: u64) {
+    //! Keeps only the todos committed at least the number of days ago.
+    for (_, issues) in found.iter_mut() {
+        issues.retain(|issue| {
+            issue.get_blame().is_some() && issue.get_age() >= days
+        });
+    }
+    found.retain(|(_, issues)| !issues.is_empty());
+}
+
+fn escape_regex(text: &str) -> String {
+    //! Escapes the characters of the text that are special in a regular
+    //! expression.
+    text.chars()
+        .map(|c| match "\\^$.|?*+()[]{}".contains(c) {
+            true => format!("\\{}", c),
+            false => c.to_string(),
+        })
+        .collect()
+}
+
+fn get_names(keywords: &[Keyword]) -> String {
+    let names: Vec<&str> = keywords.iter().map(|k| k.get_name()).collect();
+
+    names.join(", ")
+}
+
+fn format_counts(keywords: &[Keyword], counts: &[usize]) -> String {
+    //! Formats the number of comments found per keyword,
+    //! e.g. "2 TODOs, 1 FIXME".
+    let parts: Vec<String> = keywords
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| **count > 0)
+        .map(|(keyword, count)| {
+            format!(
+                "{} {}",
+                style(count).bold(),
+                cli::handle_plural(count, keyword.get_name())
+            )
+        })
+        .collect();
+
+    parts.join(", ")
+}
+
+struct Todo<'a> {
+    keyword: &'a Keyword,
+    title: String,
+    annotations: Annotations,
+    line_number: u32,
+    column: usize,
+    is_block: bool,
+    details: Vec<String>,
 }
 
 fn find_todos_in_file(
-    path: &str, 
-    prev_issues: &HashSet<String>,
-    is_verbose: bool,
+    path: &str,
+    blob_url: Option<&str>,
+    first_changed: Option<u32>,
+    branch: &str,
+    args: &Args,
 ) -> io::Result<Vec<Issue>> {
     //! Reads every line in a file for a "todo" comment, creating an Issue
-    //! object for each one with the parsed title and description.
-    //! 
+    //! object for each one annotated with the commit that added it.
+    //!
+    //! Todos below the first line changed since the last commit get no
+    //! link, since their line number differs in the commit linked to.
+    //!
     //! Returns an IO result containing a vector of Issues if successful.
     let file = File::open(path)?;
     let buffer = BufReader::new(file);
-    let mut issues_in_file = Vec::new();
+    let lines = buffer.lines().collect::<io::Result<Vec<String>>>()?;
+    let todos = find_todos_in_lines(path, &lines, args);
 
-    if is_verbose {
+    // Blaming is slow, so only files with todos are blamed.
+    let numbers: Vec<u32> = todos.iter().map(|todo| todo.line_number).collect();
+    let blames = match numbers.is_empty() {
+        true => HashMap::new(),
+        false => command::blame_lines(path, &numbers),
+    };
+
+    Ok(todos
+        .into_iter()
+        .map(|todo| {
+            let blame = blames.get(&todo.line_number).cloned();
+            let blob_url = blob_url.filter(|_| {
+                first_changed.is_none_or(|line| todo.line_number < line)
+            });
+            create_issue(todo, path, &lines, blame, blob_url, branch, args)
+        })
+        .collect())
+}
+
+fn find_todos_in_lines<'a>(
+    path: &str,
+    lines: &[String],
+    args: &'a Args,
+) -> Vec<Todo<'a>> {
+    //! Searches the lines of a file for "todo" comments, parsing the title
+    //! and description of each one.
+    //!
+    //! Comment lines directly following a todo become part of its
+    //! description when they continue the same block comment or are
+    //! indented line comments.
+    let mut todos = Vec::new();
+    let keywords = args.get_keywords();
+
+    if args.is_verbose() {
         cli::print_dim(&format!("Searching {}", path).to_string());
     }
 
-    let mut line_number = 0;
-    for line_result in buffer.lines() {
-        if let Err(e) = line_result {
-            return Err(e);
-        }
-        line_number += 1;
+    // The first line may be a shebang naming the script's interpreter.
+    let first_line = lines.first().map(|line| line.as_str()).unwrap_or("");
+    let mut scanner = Scanner::new(Syntax::detect(path, first_line));
+    let mut pending: Option<Todo> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index as u32 + 1;
+        let comments = scanner.scan(line);
 
-        let line = line_result.unwrap();
-        if contains_todo(&line) {
-            let title = extract_title(&line);
-            let body = create_body(&line_number, path);
+        if let Some((comment, keyword, index)) =
+            comments.iter().find_map(|comment| {
+                let (keyword, index) = find_keyword(comment.text, keywords)?;
+                Some((comment, keyword, index))
+            })
+        {
+            if let Some(todo) = pending.take() {
+                todos.push(todo);
+            }
+            let (annotations, title) =
+                extract_title(comment.text, keyword, index);
+            let column = line[..comment.start + index].chars().count() + 1;
+            pending = Some(Todo {
+                keyword,
+                title,
+                annotations,
+                line_number,
+                column,
+                is_block: comment.is_block,
+                details: Vec::new(),
+            });
+            continue;
+        }
 
-            if is_verbose {
-                println!("Line {}: \"{}\"", &line_number, title)
+        let continuation = match (&pending, comments.as_slice()) {
+            (Some(todo), [comment]) => get_continuation(todo, comment),
+            _ => None,
+        };
+        match continuation {
+            Some(text) => pending.as_mut().unwrap().details.push(text),
+            None => {
+                if let Some(todo) = pending.take() {
+                    todos.push(todo);
+                }
             }
-            if !prev_issues.contains(title.as_str()) {
-                let issue = Issue::new(title, body);
-                issues_in_file.push(issue);
-            } else if is_verbose {
-                cli::print_warning(
-                    "This issue was already opened in the remote repo.",
-                );
+        }
+    }
+    if let Some(todo) = pending.take() {
+        todos.push(todo);
+    }
+
+    todos
+}
+
+fn create_issue(
+    todo: Todo,
+    path: &str,
+    lines: &[String],
+    blame: Option<Blame>,
+    blob_url: Option<&str>,
+    branch: &str,
+    args: &Args,
+) -> Issue {
+    //! Creates an Issue from a parsed todo. Todos referencing an issue,
+    //! e.g. `TODO(#42)`, are marked as already opened as that issue.
+    //!
+    //! Adds the configured labels and assignees to the Issue, and assigns
+    //! it to the author of the todo if they are in the configured map of
+    //! emails to logins and the todo names no assignees of its own.
+    let is_verbose = args.is_verbose();
+    if is_verbose {
+        cli::print_info(&format!(
+            "Line {}: \"{}\"",
+            &todo.line_number, todo.title
+        ));
+    }
+
+    let reference = todo.annotations.reference;
+    let is_unassigned = todo.annotations.assignees.is_empty();
+    let end = todo.line_number + todo.details.len() as u32;
+    // Uncommitted todos aren't in the commit linked to.
+    let blob_url = blame.as_ref().and(blob_url);
+    let builder =
+        BodyBuilder::new(todo.keyword, &todo.title, path, todo.line_number)
+            .details(&todo.details)
+            .blame(blame.as_ref())
+            .permalink(blob_url)
+            .snippet(lines, end, args.get_config().get_snippet_lines())
+            .branch(branch);
+    let title = builder.build_title();
+    let body = builder.build();
+    let context = get_context(&todo, lines);
+    let fingerprint =
+        Fingerprint::new(todo.keyword.get_name(), &todo.title, path, &context);
+    let location = Location {
+        file: path.to_string(),
+        line: todo.line_number,
+        column: todo.column,
+    };
+    let mut issue = Issue::new(
+        title,
+        body,
+        todo.keyword,
+        location,
+        todo.annotations,
+        fingerprint,
+    );
+    add_config_metadata(&mut issue, args);
+    if let Some(number) = reference {
+        issue.set_existing(number);
+    }
+    if let Some(blame) = blame {
+        if let Some(login) = args.get_config().authors.get(&blame.email) {
+            if is_unassigned {
+                issue.add_assignee(login);
             }
         }
+        issue.set_blame(blame);
     }
 
-    Ok(issues_in_file)
+    issue
+}
+
+fn get_context<'a>(todo: &Todo, lines: &'a [String]) -> Vec<&'a str> {
+    //! Returns the closest nonblank lines before and after a todo
+    //! (including its continuation lines), used to fingerprint it.
+    let start = todo.line_number as usize - 1;
+    let end = start + todo.details.len() + 1;
+    let is_nonblank = |line: &&String| !line.trim().is_empty();
+    let before = lines[..start].iter().rev().find(is_nonblank);
+    let after = lines[end.min(lines.len())..].iter().find(is_nonblank);
+
+    before
+        .into_iter()
+        .chain(after)
+        .map(|line| line.as_str())
+        .collect()
+}
+
+fn add_config_metadata(issue: &mut Issue, args: &Args) {
+    //! Adds the labels, assignees and milestone every issue gets from the
+    //! config, plus the labels of the path patterns its file matches.
+    let config = args.get_config();
+    for label in &config.labels {
+        issue.add_label(label);
+    }
+    for label in args.get_path_labels(&issue.get_location().file) {
+        issue.add_label(label);
+    }
+    for assignee in &config.assignees {
+        issue.add_assignee(assignee);
+    }
+    if let Some(milestone) = &config.milestone {
+        issue.set_milestone(milestone);
+    }
 }
 
-fn contains_todo(line: &str) -> bool {
-    // Look for C and Bash style comments
-    let comment = match line.find("//") {
-        Some(value) => Some(value),
-        None => line.find("#"),
+fn get_continuation(todo: &Todo, comment: &Comment) -> Option<String> {
+    //! Returns the text of a comment that continues a todo on the
+    //! previous line, or None if the todo ends here.
+    //!
+    //! Inside a block comment every nonempty line continues the todo, with
+    //! leading `*` decoration removed. Line comments must be indented
+    //! relative to the comment marker, e.g. `//   more details`.
+    if !comment.is_whole_line || comment.is_block != todo.is_block {
+        return None;
+    }
+
+    let text = if comment.is_block {
+        comment.text.trim().trim_start_matches('*').trim()
+    } else if comment.text.starts_with("  ") || comment.text.starts_with('\t') {
+        comment.text.trim()
+    } else {
+        return None;
     };
 
-    let todo = line.find(TODO);
-    if comment.is_some() && todo.is_some() {
-        let comment_index = comment.unwrap();
-        let todo_index = todo.unwrap();
-        return todo_index > comment_index;
+    match text.is_empty() {
+        true => None,
+        false => Some(text.to_string()),
     }
+}
 
-    false
+fn find_keyword<'a>(
+    comment: &str,
+    keywords: &'a [Keyword],
+) -> Option<(&'a Keyword, usize)> {
+    //! Returns the keyword that appears first in the text of a comment,
+    //! along with its byte index.
+    keywords
+        .iter()
+        .filter_map(|keyword| Some((keyword, keyword.find_in(comment)?)))
+        .min_by_key(|(_, index)| *index)
 }
 
-fn extract_title(line: &str) -> String {
-    //! Parses a line containing a todo comment and returns the
-    //! remainder of the String after "todo" to be used as the title of a
-    //! new GitHub issue.
-    let vec: Vec<&str> = line.split(TODO).collect();
-    let after_todo = vec[1];
-    let title = if after_todo.starts_with(":") {
-        &after_todo[1..]
-    } else {
-        after_todo
+fn extract_title(
+    comment: &str,
+    keyword: &Keyword,
+    index: usize,
+) -> (Annotations, String) {
+    //! Parses the text of a comment containing a keyword and returns the
+    //! remainder of the String after the keyword to be used as the title
+    //! of a new GitHub issue.
+    //!
+    //! Annotations directly following the keyword are parsed separately:
+    //! - `TODO(alice, @bob)` assigns the issue to alice and bob
+    //! - `TODO(#42)` references an existing issue
+    //! - `TODO[P1]` sets the priority
+    //!
+    //! Parentheses containing anything other than logins and issue
+    //! references, e.g. `TODO(some thing)`, are left in the title.
+    let mut rest = &comment[index + keyword.get_name().len()..];
+    let mut annotations = Annotations::default();
+
+    loop {
+        let close = match rest.chars().next() {
+            Some('(') => ')',
+            Some('[') => ']',
+            _ => break,
+        };
+        let end = match rest.find(close) {
+            Some(end) => end,
+            None => break,
+        };
+
+        let content = &rest[1..end];
+        if close == ']' {
+            let priority = content.trim().to_uppercase();
+            if !priority.is_empty() {
+                annotations.priority = Some(priority);
+            }
+        } else {
+            let items: Vec<&str> = content
+                .split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .collect();
+            let is_annotation = items.iter().all(|item| {
+                is_reference(item) || is_login(item.trim_start_matches('@'))
+            });
+            if !is_annotation {
+                break;
+            }
+
+            for item in items {
+                match item.strip_prefix('#').map(|n| n.parse::<usize>()) {
+                    Some(Ok(number)) => annotations.reference = Some(number),
+                    _ => annotations
+                        .assignees
+                        .push(item.trim_start_matches('@').to_string()),
+                }
+            }
+        }
+        rest = &rest[end + 1..];
     }
-    .trim();
 
-    title.to_string()
+    let title = rest.strip_prefix(':').unwrap_or(rest).trim();
+
+    (annotations, title.to_string())
 }
 
-fn create_body(line_number: &u32, file_path: &str) -> String {
-    //! Creates a generic description for a new GitHub issue
-    //! based on a "todo" comment.
-    format!(
-        "Found a TODO comment on line {} of file {}",
-        line_number, file_path
-    )
-    .to_string()
+fn is_reference(item: &str) -> bool {
+    //! Returns whether the item references an issue, e.g. `#42`.
+    item.strip_prefix('#')
+        .is_some_and(|number| number.parse::<usize>().is_ok())
+}
+
+fn is_login(item: &str) -> bool {
+    //! Returns whether the item can be a username on the trackers
+    //! supported, i.e. letters, digits, `-`, `_` and `.` only.
+    !item.is_empty()
+        && item
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::Mode;
+
+    fn extract(comment: &str) -> (Annotations, String) {
+        let keyword = Keyword::new("TODO");
+        let index = find_keyword(comment, std::slice::from_ref(&keyword))
+            .unwrap()
+            .1;
+        extract_title(comment, &keyword, index)
+    }
+
+    #[test]
+    fn parses_assignees_references_and_priorities() {
+        let (annotations, title) =
+            extract(" TODO(alice, @bob.smith)[p1](#42): Fix it");
+
+        assert_eq!(annotations.assignees, ["alice", "bob.smith"]);
+        assert_eq!(annotations.reference, Some(42));
+        assert_eq!(annotations.priority.as_deref(), Some("P1"));
+        assert_eq!(title, "Fix it");
+    }
+
+    #[test]
+    fn leaves_other_parentheses_in_the_title() {
+        let (annotations, title) = extract(" TODO(some thing): Fix it");
+
+        assert!(annotations.assignees.is_empty());
+        assert_eq!(title, "(some thing): Fix it");
+
+        let (annotations, title) = extract(" TODO(alice, see #2): Fix it");
+        assert!(annotations.assignees.is_empty());
+        assert_eq!(annotations.reference, None);
+        assert_eq!(title, "(alice, see #2): Fix it");
+    }
+
+    #[test]
+    fn leaves_unclosed_annotations_in_the_title() {
+        let (_, title) = extract(" TODO(alice: Fix it");
+        assert_eq!(title, "(alice: Fix it");
+    }
+
+    fn change(
+        commit: &str,
+        path: &str,
+        removed: &[&str],
+        added: &[&str],
+    ) -> Change {
+        let lines =
+            |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect();
+        Change {
+            commit: commit.to_string(),
+            path: path.to_string(),
+            removed: lines(removed),
+            added: lines(added),
+        }
+    }
+
+    #[test]
+    fn get_removals_leaves_out_moved_todos() {
+        let args = Args::new(Mode::Sync, &["TODO"]);
+        let changes = [
+            change("c2", "a.rs", &["// TODO: Moved", "// TODO: Gone"], &[]),
+            change("c2", "b.rs", &[], &["    // TODO: moved."]),
+            change("c1", "a.rs", &["// TODO: Old"], &["// TODO: Gone"]),
+            change("c1", "c.py", &[r#"x = "TODO: string""#], &[]),
+        ];
+
+        let removals: Vec<(String, String)> = get_removals(&changes, &args)
+            .into_iter()
+            .map(|removal| (removal.commit, removal.title))
+            .collect();
+        assert_eq!(
+            removals,
+            [
+                ("c2".to_string(), "Gone".to_string()),
+                ("c1".to_string(), "Old".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn get_removals_pairs_changes_within_a_commit_only() {
+        let args = Args::new(Mode::Sync, &["TODO"]);
+        let changes = [
+            change("c2", "b.rs", &[], &["// TODO: Back"]),
+            change("c1", "a.rs", &["// TODO: Back"], &[]),
+        ];
+        let removals = get_removals(&changes, &args);
+
+        assert_eq!(removals.len(), 1);
+        assert_eq!(removals[0].commit, "c1");
+        let fingerprint = Fingerprint::new("TODO", "Back", "x.rs", &["y"]);
+        assert!(removals[0].fingerprint.has_same_text(&fingerprint));
+    }
 }
@@ -0,0 +1,158 @@
+use serde_json::{json, value::Value};
+
+use super::cli;
+use super::issue;
+use super::keyword;
+
+use cli::Format;
+use issue::Issue;
+use keyword::Keyword;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_LEVEL: &str = "note";
+// Versioned so consumers can tell fingerprints apart if the scheme changes.
+const FINGERPRINT_KEY: &str = "todoIssueFingerprint/v1";
+const INFORMATION_URI: &str = "https://github.com/wgalyen/todo-issue";
+
+pub fn print_todos(
+    found: &[(String, Vec<Issue>)],
+    keywords: &[Keyword],
+    format: Format,
+) {
+    //! Prints every todo found to stdout, either as a single JSON array,
+    //! as JSON Lines with one todo per line or as a SARIF log.
+    let todos = found.iter().flat_map(|(_, issues)| issues.iter());
+
+    match format {
+        Format::Json => {
+            let array: Vec<Value> = todos.map(to_json).collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&array).unwrap_or_default()
+            );
+        }
+        Format::JsonLines => {
+            for todo in todos {
+                println!("{}", to_json(todo));
+            }
+        }
+        Format::Sarif => {
+            let log = to_sarif(todos.collect(), keywords);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&log).unwrap_or_default()
+            );
+        }
+        Format::Text => {}
+    }
+}
+
+fn to_sarif(todos: Vec<&Issue>, keywords: &[Keyword]) -> Value {
+    //! Creates a SARIF 2.1.0 log with a rule per keyword and a result per
+    //! todo. Results carry the todo's fingerprint as a partial fingerprint,
+    //! so code scanning tracks them across runs the same way todo-issue
+    //! matches them with issues.
+    let rules: Vec<Value> = keywords
+        .iter()
+        .map(|keyword| {
+            json!({
+                "id": keyword.get_name(),
+                "shortDescription": {
+                    "text": format!("{} comment", keyword.get_name()),
+                },
+                "defaultConfiguration": { "level": SARIF_LEVEL },
+                "properties": { "tags": [keyword.get_label()] },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = todos
+        .into_iter()
+        .map(|todo| {
+            let location = todo.get_location();
+            let message = match todo.get_title().is_empty() {
+                true => format!("{} comment", todo.get_keyword()),
+                false => todo.get_title(),
+            };
+            let mut result = json!({
+                "ruleId": todo.get_keyword(),
+                "level": SARIF_LEVEL,
+                "message": { "text": message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": &location.file,
+                            "uriBaseId": "%SRCROOT%",
+                        },
+                        "region": {
+                            "startLine": location.line,
+                            "startColumn": location.column,
+                        },
+                    },
+                }],
+                "partialFingerprints": {
+                    FINGERPRINT_KEY: todo.get_fingerprint().to_string(),
+                },
+            });
+            if let Some(index) = keywords
+                .iter()
+                .position(|k| k.get_name() == todo.get_keyword())
+            {
+                result["ruleIndex"] = json!(index);
+            }
+            result
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": INFORMATION_URI,
+                    "rules": rules,
+                },
+            },
+            // Columns count characters rather than UTF-16 code units.
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    })
+}
+
+fn to_json(issue: &Issue) -> Value {
+    let location = issue.get_location();
+    let annotations = issue.get_annotations();
+    let blame = issue.get_blame().map(|blame| {
+        json!({
+            "author": &blame.author,
+            "email": &blame.email,
+            "commit": &blame.commit,
+            "date": blame.get_date(),
+            "age_days": blame.get_age(),
+        })
+    });
+
+    json!({
+        "file": &location.file,
+        "line": location.line,
+        "column": location.column,
+        "keyword": issue.get_keyword(),
+        "title": issue.get_title(),
+        "body": issue.get_body(),
+        "labels": issue.get_labels(),
+        "milestone": issue.get_milestone(),
+        "fingerprint": issue.get_fingerprint().to_string(),
+        "annotations": {
+            "assignees": &annotations.assignees,
+            "reference": annotations.reference,
+            "priority": &annotations.priority,
+        },
+        "blame": blame,
+        "existing_issue": issue.get_existing(),
+    })
+}
@@ -1,55 +1,134 @@
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
-use console::style;
 use reqwest::header::AUTHORIZATION;
-use reqwest::StatusCode;
 use serde_json::json;
 
-use super::cli;
+use super::command;
 use super::issue;
+use super::tracker;
 
+use command::Remote;
 use issue::Issue;
+use tracker::IssueTracker;
 
-const API_ENDPOINT: &str = "https://api.github.com";
+const GITHUB_API_ENDPOINT: &str = "https://api.github.com";
 
 pub struct Request {
     client: reqwest::Client,
     url: String,
+    milestones_url: String,
     remote_url: String,
     auth_header: String,
+    per_page: usize,
+    // Milestone numbers by title, looked up once per title.
+    milestones: RefCell<HashMap<String, usize>>,
 }
 
 impl Request {
-    pub fn new(token: String, remote: String) -> Request {
+    pub fn new(
+        token: String,
+        remote: &Remote,
+        api_url: Option<&str>,
+        per_page: usize,
+    ) -> Request {
         //! Creates a new request object that encapsulates the http client,
         //! url formatted with the API endpoint and user's remote repo,
-        //! and auth header containing the user's token.
+        //! auth header containing the user's token and the number of
+        //! issues to request per page.
+        //!
+        //! The API endpoint is derived from the remote's host unless one
+        //! is given, e.g. to point at a mock server.
+        let api_url = match api_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => get_api_url(&remote.host),
+        };
+
         Request {
             client: reqwest::Client::new(),
-            url: format!("{}/repos/{}/issues", API_ENDPOINT, remote)
-                .to_string(),
-            remote_url: format!("https://github.com/{}", remote).to_string(),
+            url: format!("{}/repos/{}/issues", api_url, remote.path),
+            milestones_url: format!(
+                "{}/repos/{}/milestones",
+                api_url, remote.path
+            ),
+            remote_url: format!("https://{}/{}", remote.host, remote.path),
             auth_header: format!("token {}", token).to_string(),
+            per_page,
+            milestones: RefCell::new(HashMap::new()),
         }
     }
 
-    pub fn open_issue(&self, issue: &Issue) -> Option<usize> {
+    fn get_milestone_number(&self, title: &str) -> Option<usize> {
+        //! Looks up the number of the milestone with the title, which GitHub
+        //! expects when creating an issue.
+        if let Some(number) = self.milestones.borrow().get(title) {
+            return Some(*number);
+        }
+
+        let params = json!({ "state": "all", "per_page": self.per_page });
+        let milestone = tracker::find_milestone(
+            &self.milestones_url,
+            &params,
+            |url| {
+                self.client
+                    .get(url)
+                    .header(AUTHORIZATION, self.auth_header.clone())
+            },
+            title,
+        )?;
+        let number = milestone.number.unwrap_or(milestone.id);
+        self.milestones
+            .borrow_mut()
+            .insert(title.to_string(), number);
+
+        Some(number)
+    }
+}
+
+impl IssueTracker for Request {
+    fn get_remote_url(&self) -> &str {
+        &self.remote_url
+    }
+
+    fn get_blob_url(&self, commit: &str) -> Option<String> {
+        Some(format!("{}/blob/{}", self.remote_url, commit))
+    }
+
+    fn get_issues_with_label(
+        &self,
+        label: &str,
+    ) -> Option<Vec<issue::Response>> {
+        //! Makes GET requests to retrieve every page of issues with the
+        //! label.
+        let params = json!({
+            "labels": label,
+            "state": "all",
+            "per_page": self.per_page,
+        });
+
+        tracker::get_all_pages(&self.url, &params, |url| {
+            self.client
+                .get(url)
+                .header(AUTHORIZATION, self.auth_header.clone())
+        })
+    }
+
+    fn open_issue(&self, issue: &Issue) -> Option<usize> {
         //! Makes a POST request to create a new issue with
-        //! the inputted params (title and description).
-        //! 
-        //! Panics if the response is not 201 Created or the request fails.
+        //! the inputted params (title, description, labels, assignees and
+        //! milestone).
+        //!
         //! Returns a number which represents the issue number from GitHub.
-        let mut response = self
+        let mut json = issue.to_json();
+        if let Some(milestone) = issue.get_milestone() {
+            json["milestone"] = json!(self.get_milestone_number(milestone)?);
+        }
+        let builder = self
             .client
             .post(&self.url)
             .header(AUTHORIZATION, self.auth_header.clone())
-            .json(&issue.to_json())
-            .send()
-            .expect("Failed to create issue");
-
-        if !Self::is_successful_response(response.status()) {
-            return None;
-        }
+            .json(&json);
+        let mut response = tracker::send_successfully(builder, &self.url)?;
 
         match response.json::<issue::Response>() {
             Ok(json) => Some(json.get_number()),
@@ -57,90 +136,134 @@ impl Request {
         }
     }
 
-    pub fn get_issues(&self, is_dry_run: bool) -> Option<HashSet<String>> {
-        //! Makes a GET request to retrieve all issues (open and closed)
-        //! with a todo label in the remove repository.
-        //! 
-        //! Returns a hashset of the issue titles. Returns early if the response
-        //! is not 200 OK or the request fails.
-        if is_dry_run {
-            return Some(HashSet::new());
-        }
+    fn update_issue(&self, number: usize, body: &str) -> bool {
+        //! Makes a PATCH request to replace the body of an issue.
+        //!
+        //! Returns a bool indicating whether the request was successful.
+        let url = format!("{}/{}", self.url, number);
+        let builder = self
+            .client
+            .patch(&url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "body": body }));
 
-        println!(
-            "Fetching all issues with {} label from {}",
-            style(issue::LABEL).cyan(),
-            style(&self.remote_url).italic()
-        );
+        tracker::send_successfully(builder, &url).is_some()
+    }
 
-        let params = json!({
-            "labels": issue::LABEL,
-            "state": "all",
-        });
-        let mut response = self
+    fn close_issue(&self, number: usize, comment: &str) -> bool {
+        //! Makes a POST request to comment on an issue, followed by a PATCH
+        //! request to close it.
+        //!
+        //! Returns a bool indicating whether both requests were successful.
+        let url = format!("{}/{}", self.url, number);
+        let comments_url = format!("{}/comments", url);
+        let builder = self
             .client
-            .get(&self.url)
+            .post(&comments_url)
             .header(AUTHORIZATION, self.auth_header.clone())
-            .query(&params)
-            .send()
-            .expect("Failed to get issues");
-
-        if !Self::is_successful_response(response.status()) {
-            return None;
+            .json(&json!({ "body": comment }));
+        if tracker::send_successfully(builder, &comments_url).is_none() {
+            return false;
         }
 
-        let mut issues = HashSet::new();
-        if let Ok(json_array) = response.json::<Vec<issue::Response>>() {
-            for result in json_array {
-                issues.insert(result.get_title());
-            }
+        let builder = self
+            .client
+            .patch(&url)
+            .header(AUTHORIZATION, self.auth_header.clone())
+            .json(&json!({ "state": "closed" }));
+
+        tracker::send_successfully(builder, &url).is_some()
+    }
+}
+
+fn get_api_url(host: &str) -> String {
+    //! Returns the REST API endpoint of a GitHub host. GitHub Enterprise
+    //! Server serves its API under `/api/v3` of its own host.
+    match host == command::GITHUB_HOST {
+        true => GITHUB_API_ENDPOINT.to_string(),
+        false => format!("https://{}/api/v3", host),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{Reply, Server};
+
+    fn remote(host: &str) -> Remote {
+        Remote {
+            host: host.to_string(),
+            path: "owner/repo".to_string(),
         }
+    }
 
-        match issues.len() {
-            0 => println!(
-                "No previously opened issues found in the remote repo."
-            ),
-            n => println!(
-                "Found {} previously opened {} in the remote repo.",
-                style(n).bold(),
-                cli::handle_plural(&n, "issue")
-            ),
-        };
-        Some(issues)
+    #[test]
+    fn get_api_url_uses_enterprise_path() {
+        assert_eq!(get_api_url("github.com"), "https://api.github.com");
+        assert_eq!(get_api_url("ghe.corp"), "https://ghe.corp/api/v3");
     }
 
-    fn is_successful_response(status: StatusCode) -> bool {
-        //! Asserts that the status code returned is either
-        //! 200 OK or 201 Created.
-        //! 
-        //! Otherwise, outputs a detailed description about the error.
-        match status {
-            StatusCode::OK | StatusCode::CREATED => return true,
-            StatusCode::UNAUTHORIZED => cli::print_error(
-                "Unathorized request. \
-                 Make sure your access token is valid and \
-                 you have pull access to the repository.",
-            ),
-            StatusCode::GONE => {
-                cli::print_error("Issues are disabled in this repository.");
-            }
-            StatusCode::FORBIDDEN => cli::print_error(
-                "You have reached the GitHub API rate limit. \
-                 Please try again later.",
-            ),
-            StatusCode::NOT_FOUND => cli::print_error(
-                "Remote repository not found. \
-                 If your repository is private check that \
-                 your access token has the correct permissions.",
-            ),
-            StatusCode::UNPROCESSABLE_ENTITY => {
-                cli::print_error("Unable to process request.");
-            }
-            s => cli::print_error(
-                &format!("Received unexpected status code {}", s).to_string(),
+    #[test]
+    fn get_issues_follows_next_links() {
+        let server = Server::start(vec![
+            Reply::json(
+                r#"[
+                    {"title": "One", "number": 1, "body": null},
+                    {"title": "Two", "number": 2, "body": "Body",
+                     "state": "closed"}
+                ]"#,
+            )
+            .header(
+                "Link",
+                "<{url}/repos/owner/repo/issues?page=2>; rel=\"next\", \
+                 <{url}/repos/owner/repo/issues?page=2>; rel=\"last\"",
             ),
-        };
+            Reply::json(r#"[{"title": "Three", "number": 3, "body": null}]"#),
+        ]);
+        let request = Request::new(
+            "secret".to_string(),
+            &remote("ghe.corp"),
+            Some(&format!("{}/", server.url)),
+            2,
+        );
+
+        let issues = request.get_issues_with_label("TODO").unwrap();
+        let titles: Vec<String> =
+            issues.iter().map(|issue| issue.get_title()).collect();
+        assert_eq!(titles, ["One", "Two", "Three"]);
+        assert!(!issues[1].is_open());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(
+            requests[0].path,
+            "/repos/owner/repo/issues?labels=TODO&per_page=2&state=all"
+        );
+        assert_eq!(requests[1].path, "/repos/owner/repo/issues?page=2");
+        for request in &requests {
+            assert_eq!(request.header("Authorization"), Some("token secret"));
+        }
+    }
+
+    #[test]
+    fn close_issue_comments_then_closes() {
+        let server = Server::start(vec![Reply::json("{}"), Reply::status(401)]);
+        let request = Request::new(
+            "secret".to_string(),
+            &remote("github.com"),
+            Some(&server.url),
+            100,
+        );
+
+        assert!(!request.close_issue(7, "Removed."));
 
-        false
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/repos/owner/repo/issues/7/comments");
+        assert_eq!(requests[0].json()["body"], "Removed.");
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(requests[1].path, "/repos/owner/repo/issues/7");
+        assert_eq!(requests[1].body, r#"{"state":"closed"}"#);
     }
 }
@@ -0,0 +1,315 @@
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Confirmation};
+
+use super::cli;
+use super::command;
+use super::fingerprint;
+use super::issue;
+use super::parse;
+use super::tracker;
+
+use cli::Args;
+use issue::{Issue, Response};
+use parse::Removal;
+use tracker::IssueTracker;
+
+pub fn update_moved_todos(
+    tracker: &dyn IssueTracker,
+    found: &[(String, Vec<Issue>)],
+    existing: &[Response],
+    is_verbose: bool,
+) -> bool {
+    //! Updates the body of every open issue whose comment changed place
+    //! since it was opened, so its location stays current. Notes people
+    //! added below the marker at the end of the body are kept.
+    //!
+    //! Returns a bool indicating whether every update was successful.
+    for issue in found.iter().flat_map(|(_, issues)| issues.iter()) {
+        let remote = match issue.get_existing().and_then(|number| {
+            existing.iter().find(|r| r.get_number() == number)
+        }) {
+            Some(remote) if remote.is_open() => remote,
+            _ => continue,
+        };
+        let body = match remote.get_updated_body(issue) {
+            Some(body) => body,
+            None => continue,
+        };
+
+        if !tracker.update_issue(remote.get_number(), &body) {
+            return false;
+        }
+        if is_verbose {
+            cli::print_dim(&format!(
+                "Updated the location of issue {}: \"{}\"",
+                tracker.format_number(remote.get_number()),
+                remote.get_title()
+            ));
+        }
+    }
+
+    true
+}
+
+pub fn close_removed_todos(
+    tracker: &dyn IssueTracker,
+    found: &[(String, Vec<Issue>)],
+    existing: &[Response],
+    args: &Args,
+) -> bool {
+    //! Closes every open issue opened by todo-issue whose comment no
+    //! longer exists in the code, leaving a comment that names the commit
+    //! that removed it.
+    //!
+    //! Only previews the issues to close on a dry run, and asks for
+    //! confirmation before closing them otherwise, unless running with
+    //! --yes in which case at most the maximum number of issues is closed.
+    //! Returns a bool indicating whether every issue was closed successfully.
+    let orphans = find_orphans(found, existing);
+    if orphans.is_empty() {
+        cli::print_success("Every open issue still has its comment.");
+        return true;
+    }
+
+    cli::print_info(&format!(
+        "Found {} open {} whose comment was removed:",
+        style(orphans.len()).bold(),
+        cli::handle_plural(&orphans.len(), "issue")
+    ));
+    for issue in &orphans {
+        cli::print_info(&format!(
+            "  {} {}",
+            tracker.format_number(issue.get_number()),
+            issue.get_title()
+        ));
+    }
+
+    if args.is_dry_run() {
+        return true;
+    }
+
+    if args.is_batch() {
+        if orphans.len() > args.get_max_issues() {
+            cli::print_error(&format!(
+                "That's more than the maximum of {}. No issues were closed. \
+                 Raise the limit with --max-issues.",
+                args.get_max_issues()
+            ));
+            return false;
+        }
+    } else {
+        if !cli::is_interactive() {
+            cli::print_error(
+                "Not running in a terminal. \
+                 Use --yes to close the issues without prompting.",
+            );
+            return false;
+        }
+
+        let prompt = format!(
+            "Close {} {}?",
+            orphans.len(),
+            cli::handle_plural(&orphans.len(), "issue")
+        );
+        let is_confirmed = Confirmation::with_theme(&ColorfulTheme::default())
+            .with_text(&prompt)
+            .default(false)
+            .interact()
+            .unwrap();
+        if !is_confirmed {
+            return true;
+        }
+    }
+
+    let head = command::get_head_commit();
+    let committed = match find_committed(existing, args) {
+        Some(committed) => committed,
+        None => return false,
+    };
+    let removals = parse::find_removed_todos(args);
+    for issue in orphans {
+        let is_committed = committed.contains(&issue.get_number());
+        let removing_commit = match is_committed {
+            true => None,
+            false => find_removing_commit(issue, &removals),
+        };
+        let comment = get_close_comment(is_committed, removing_commit, &head);
+        if !tracker.close_issue(issue.get_number(), &comment) {
+            return false;
+        }
+        cli::print_success(&format!(
+            "Closed issue {}: \"{}\"",
+            tracker.format_number(issue.get_number()),
+            issue.get_title()
+        ));
+    }
+
+    true
+}
+
+fn find_orphans<'a>(
+    found: &[(String, Vec<Issue>)],
+    existing: &'a [Response],
+) -> Vec<&'a Response> {
+    //! Returns the open issues opened by todo-issue that no todo found
+    //! matches.
+    let matched: Vec<usize> = found
+        .iter()
+        .flat_map(|(_, issues)| issues.iter())
+        .filter_map(|issue| issue.get_existing())
+        .collect();
+
+    // Issues without a fingerprint weren't opened by todo-issue, so they
+    // are never closed automatically.
+    existing
+        .iter()
+        .filter(|issue| issue.is_open() && issue.get_fingerprint().is_some())
+        .filter(|issue| !matched.contains(&issue.get_number()))
+        .collect()
+}
+
+fn get_close_comment(
+    is_committed: bool,
+    removing_commit: Option<&str>,
+    head: &str,
+) -> String {
+    //! Returns the comment explaining why an issue is closed. Comments
+    //! that still exist in the current commit were only removed from the
+    //! working tree, whatever commit removed the same text before.
+    match (is_committed, removing_commit) {
+        (true, _) => format!(
+            "The comment for this issue was removed from the working tree, \
+             but still exists in {}.",
+            head
+        ),
+        (false, Some(commit)) => {
+            format!("The comment for this issue was removed in {}.", commit)
+        }
+        (false, None) => format!(
+            "The comment for this issue no longer exists as of {}.",
+            head
+        ),
+    }
+}
+
+fn find_committed(existing: &[Response], args: &Args) -> Option<Vec<usize>> {
+    //! Returns the numbers of the issues whose comment exists in the
+    //! current commit. For issues whose comment wasn't found in the working
+    //! tree, this means it was removed without committing yet.
+    let mut found = parse::find_all_todos_at("HEAD", args)?;
+    let mut todos: Vec<&mut Issue> = found
+        .iter_mut()
+        .flat_map(|(_, issues)| issues.iter_mut())
+        .collect();
+    fingerprint::match_existing(&mut todos, existing);
+
+    Some(
+        todos
+            .iter()
+            .filter_map(|todo| todo.get_existing())
+            .collect(),
+    )
+}
+
+fn find_removing_commit<'a>(
+    issue: &Response,
+    removals: &'a [Removal],
+) -> Option<&'a str> {
+    //! Returns the latest commit that removed a comment with the issue's
+    //! text, after confirming with `git grep` that the comment's text is in
+    //! the commit's parent but not in the commit itself. Otherwise the
+    //! text was changed rather than removed, or exists elsewhere.
+    let fingerprint = issue.get_fingerprint()?;
+    let removal = removals
+        .iter()
+        .find(|removal| removal.fingerprint.has_same_text(&fingerprint))?;
+    let parent = format!("{}^", removal.commit);
+    let is_removed = !removal.title.is_empty()
+        && command::contains_text(&parent, &removal.title)
+        && !command::contains_text(&removal.commit, &removal.title);
+
+    match is_removed {
+        true => Some(&removal.commit),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::Fingerprint;
+    use crate::issue::{Annotations, Location};
+    use crate::keyword::Keyword;
+
+    fn todo(title: &str, existing: Option<usize>) -> Issue {
+        let location = Location {
+            file: "a.rs".to_string(),
+            line: 1,
+            column: 0,
+        };
+        let fingerprint = Fingerprint::new("TODO", title, "a.rs", &[]);
+        let mut issue = Issue::new(
+            title.to_string(),
+            String::new(),
+            &Keyword::new("TODO"),
+            location,
+            Annotations::default(),
+            fingerprint,
+        );
+        if let Some(number) = existing {
+            issue.set_existing(number);
+        }
+        issue
+    }
+
+    fn remote(number: usize, is_open: bool, has_fingerprint: bool) -> Response {
+        let title = format!("Issue {}", number);
+        let body = match has_fingerprint {
+            true => todo(&title, None).get_full_body(),
+            false => "Opened by hand".to_string(),
+        };
+        Response::new(title, number, Some(body), is_open)
+    }
+
+    #[test]
+    fn find_orphans_only_returns_unmatched_open_issues() {
+        let found = vec![("a.rs".to_string(), vec![todo("Kept", Some(1))])];
+        let existing = [
+            remote(1, true, true),
+            remote(2, true, true),
+            remote(3, false, true),
+            remote(4, true, false),
+            Response::new("Empty".to_string(), 5, None, true),
+        ];
+
+        let orphans: Vec<usize> = find_orphans(&found, &existing)
+            .iter()
+            .map(|issue| issue.get_number())
+            .collect();
+        assert_eq!(orphans, [2]);
+    }
+
+    #[test]
+    fn find_orphans_never_returns_issues_without_fingerprints() {
+        let existing = [remote(1, true, false), remote(2, true, false)];
+
+        assert!(find_orphans(&[], &existing).is_empty());
+    }
+
+    #[test]
+    fn close_comment_names_where_the_comment_went() {
+        assert_eq!(
+            get_close_comment(true, Some("abc"), "def"),
+            "The comment for this issue was removed from the working tree, \
+             but still exists in def."
+        );
+        assert_eq!(
+            get_close_comment(false, Some("abc"), "def"),
+            "The comment for this issue was removed in abc."
+        );
+        assert_eq!(
+            get_close_comment(false, None, "def"),
+            "The comment for this issue no longer exists as of def."
+        );
+    }
+}
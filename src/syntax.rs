@@ -0,0 +1,564 @@
+use std::path::Path;
+
+pub struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+    // Whether `'` starts a char literal like `'"'`, or a lifetime otherwise.
+    char_literals: bool,
+}
+
+pub struct Comment<'a> {
+    pub text: &'a str,
+    // The byte index of the text in the line.
+    pub start: usize,
+    pub is_block: bool,
+    pub is_whole_line: bool,
+}
+
+pub struct Scanner {
+    syntax: &'static Syntax,
+    block_end: Option<&'static str>,
+    // The end of a string literal spanning lines, e.g. `"""` in Python.
+    string_end: Option<&'static str>,
+}
+
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+
+const GENERIC: Syntax = Syntax {
+    line_comments: &["//", "#"],
+    block_comments: &[],
+    quotes: &['"'],
+    char_literals: false,
+};
+const RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: C_BLOCK,
+    quotes: &['"'],
+    char_literals: true,
+};
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: C_BLOCK,
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const JS_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: C_BLOCK,
+    quotes: &['"', '\'', '`'],
+    char_literals: false,
+};
+const PHP: Syntax = Syntax {
+    line_comments: &["//", "#"],
+    block_comments: C_BLOCK,
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const HASH: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[("\"\"\"", "\"\"\""), ("'''", "'''")],
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const RUBY: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[("=begin", "=end")],
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const LUA: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("--[[", "]]")],
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: C_BLOCK,
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const MARKUP: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: &[("<!--", "-->")],
+    quotes: &[],
+    char_literals: false,
+};
+const DOUBLE_DASH: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: C_BLOCK,
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+const HASKELL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("{-", "-}")],
+    quotes: &['"'],
+    char_literals: false,
+};
+const LISP: Syntax = Syntax {
+    line_comments: &[";"],
+    block_comments: &[("#|", "|#")],
+    quotes: &['"'],
+    char_literals: false,
+};
+const INI: Syntax = Syntax {
+    line_comments: &[";", "#"],
+    block_comments: &[],
+    quotes: &['"'],
+    char_literals: false,
+};
+const BASIC: Syntax = Syntax {
+    line_comments: &["'"],
+    block_comments: &[],
+    quotes: &['"'],
+    char_literals: false,
+};
+const PERCENT: Syntax = Syntax {
+    line_comments: &["%"],
+    block_comments: &[],
+    quotes: &['"'],
+    char_literals: false,
+};
+const FORTRAN: Syntax = Syntax {
+    line_comments: &["!"],
+    block_comments: &[],
+    quotes: &['"', '\''],
+    char_literals: false,
+};
+
+const EXTENSIONS: &[(&str, &Syntax)] = &[
+    ("rs", &RUST),
+    ("c", &C_LIKE),
+    ("h", &C_LIKE),
+    ("cc", &C_LIKE),
+    ("cpp", &C_LIKE),
+    ("cxx", &C_LIKE),
+    ("hh", &C_LIKE),
+    ("hpp", &C_LIKE),
+    ("cs", &C_LIKE),
+    ("java", &C_LIKE),
+    ("kt", &C_LIKE),
+    ("kts", &C_LIKE),
+    ("scala", &C_LIKE),
+    ("swift", &C_LIKE),
+    ("dart", &C_LIKE),
+    ("groovy", &C_LIKE),
+    ("gradle", &C_LIKE),
+    ("proto", &C_LIKE),
+    ("sol", &C_LIKE),
+    ("zig", &C_LIKE),
+    ("css", &CSS),
+    ("scss", &C_LIKE),
+    ("less", &C_LIKE),
+    ("js", &JS_LIKE),
+    ("jsx", &JS_LIKE),
+    ("mjs", &JS_LIKE),
+    ("cjs", &JS_LIKE),
+    ("ts", &JS_LIKE),
+    ("tsx", &JS_LIKE),
+    ("go", &JS_LIKE),
+    ("php", &PHP),
+    ("py", &PYTHON),
+    ("rb", &RUBY),
+    ("sh", &HASH),
+    ("bash", &HASH),
+    ("zsh", &HASH),
+    ("fish", &HASH),
+    ("pl", &HASH),
+    ("pm", &HASH),
+    ("r", &HASH),
+    ("jl", &HASH),
+    ("ex", &HASH),
+    ("exs", &HASH),
+    ("cr", &HASH),
+    ("nim", &HASH),
+    ("nix", &HASH),
+    ("tf", &HASH),
+    ("ps1", &HASH),
+    ("cmake", &HASH),
+    ("mk", &HASH),
+    ("yml", &HASH),
+    ("yaml", &HASH),
+    ("toml", &HASH),
+    ("conf", &HASH),
+    ("properties", &HASH),
+    ("sql", &DOUBLE_DASH),
+    ("lua", &LUA),
+    ("ada", &DOUBLE_DASH),
+    ("adb", &DOUBLE_DASH),
+    ("ads", &DOUBLE_DASH),
+    ("vhd", &DOUBLE_DASH),
+    ("vhdl", &DOUBLE_DASH),
+    ("hs", &HASKELL),
+    ("elm", &HASKELL),
+    ("purs", &HASKELL),
+    ("lisp", &LISP),
+    ("lsp", &LISP),
+    ("cl", &LISP),
+    ("el", &LISP),
+    ("clj", &LISP),
+    ("cljs", &LISP),
+    ("cljc", &LISP),
+    ("edn", &LISP),
+    ("scm", &LISP),
+    ("ss", &LISP),
+    ("rkt", &LISP),
+    ("asm", &LISP),
+    ("ini", &INI),
+    ("vb", &BASIC),
+    ("vbs", &BASIC),
+    ("bas", &BASIC),
+    ("tex", &PERCENT),
+    ("sty", &PERCENT),
+    ("cls", &PERCENT),
+    ("erl", &PERCENT),
+    ("hrl", &PERCENT),
+    ("html", &MARKUP),
+    ("htm", &MARKUP),
+    ("xhtml", &MARKUP),
+    ("xml", &MARKUP),
+    ("svg", &MARKUP),
+    ("vue", &MARKUP),
+    ("md", &MARKUP),
+    ("markdown", &MARKUP),
+    ("f90", &FORTRAN),
+    ("f95", &FORTRAN),
+    ("f03", &FORTRAN),
+];
+
+const FILE_NAMES: &[(&str, &Syntax)] = &[
+    ("Makefile", &HASH),
+    ("Dockerfile", &HASH),
+    ("Gemfile", &HASH),
+    ("Rakefile", &RUBY),
+    ("CMakeLists.txt", &HASH),
+];
+
+const INTERPRETERS: &[(&str, &Syntax)] = &[
+    ("sh", &HASH),
+    ("bash", &HASH),
+    ("zsh", &HASH),
+    ("dash", &HASH),
+    ("ksh", &HASH),
+    ("fish", &HASH),
+    ("python", &PYTHON),
+    ("ruby", &RUBY),
+    ("perl", &HASH),
+    ("Rscript", &HASH),
+    ("php", &PHP),
+    ("node", &JS_LIKE),
+    ("deno", &JS_LIKE),
+    ("lua", &LUA),
+    ("runghc", &HASKELL),
+    ("escript", &PERCENT),
+];
+
+impl Syntax {
+    pub fn detect(path: &str, first_line: &str) -> &'static Syntax {
+        //! Picks the comment syntax for a file based on its extension,
+        //! its file name, or the interpreter named in its shebang line.
+        //!
+        //! Falls back to a generic syntax recognising `//` and `#` comments
+        //! for unknown file types.
+        Self::from_path(path)
+            .or_else(|| Self::from_shebang(first_line))
+            .unwrap_or(&GENERIC)
+    }
+
+    fn from_path(path: &str) -> Option<&'static Syntax> {
+        let path = Path::new(path);
+        let file_name = path.file_name()?.to_str()?;
+        if let Some((_, syntax)) =
+            FILE_NAMES.iter().find(|(name, _)| *name == file_name)
+        {
+            return Some(*syntax);
+        }
+
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(ext, _)| *ext == extension)
+            .map(|(_, syntax)| *syntax)
+    }
+
+    fn from_shebang(line: &str) -> Option<&'static Syntax> {
+        //! Parses lines like `#!/bin/sh` or `#!/usr/bin/env python3`
+        //! and looks up the interpreter by name, ignoring version suffixes.
+        let command = line.strip_prefix("#!")?;
+        let mut words = command.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+        if program == "env" {
+            program = words.find(|word| !word.starts_with('-'))?;
+        }
+        let program =
+            program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        INTERPRETERS
+            .iter()
+            .find(|(name, _)| *name == program)
+            .map(|(_, syntax)| *syntax)
+    }
+}
+
+impl Scanner {
+    pub fn new(syntax: &'static Syntax) -> Scanner {
+        Scanner {
+            syntax,
+            block_end: None,
+            string_end: None,
+        }
+    }
+
+    pub fn scan<'a>(&mut self, line: &'a str) -> Vec<Comment<'a>> {
+        //! Returns every comment found on the line, in order.
+        //!
+        //! Keeps track of block comments left open at the end of the line
+        //! so the following lines are treated as part of the same comment.
+        //! Comment markers inside string literals are skipped.
+        let mut comments = Vec::new();
+        let mut start = 0;
+
+        if let Some(end) = self.string_end {
+            match line.find(end) {
+                Some(index) => {
+                    start = index + end.len();
+                    self.string_end = None;
+                }
+                None => return comments,
+            }
+        }
+
+        if let Some(end) = self.block_end {
+            match line.find(end) {
+                Some(index) => {
+                    comments.push(Comment {
+                        text: &line[..index],
+                        start: 0,
+                        is_block: true,
+                        is_whole_line: true,
+                    });
+                    start = index + end.len();
+                    self.block_end = None;
+                }
+                None => {
+                    comments.push(Comment {
+                        text: line,
+                        start: 0,
+                        is_block: true,
+                        is_whole_line: true,
+                    });
+                    return comments;
+                }
+            }
+        }
+
+        let mut quote: Option<char> = None;
+        let mut is_escaped = false;
+        let mut skip_to = start;
+
+        for (index, c) in line[start..].char_indices() {
+            let index = start + index;
+            if index < skip_to {
+                continue;
+            }
+            if let Some(open) = quote {
+                if is_escaped {
+                    is_escaped = false;
+                } else if c == '\\' {
+                    is_escaped = true;
+                } else if c == open {
+                    quote = None;
+                }
+                continue;
+            }
+
+            let rest = &line[index..];
+            let is_whole_line = line[..index].trim().is_empty();
+            if let Some((open, end)) =
+                self.syntax.block_comments.iter().find(|(open, _)| {
+                    // Markers like `=begin` are only valid at the line start.
+                    rest.starts_with(*open)
+                        && (!open.starts_with('=') || index == 0)
+                })
+            {
+                let text_start = index + open.len();
+                // Docstrings are only comments when they start a statement,
+                // otherwise they're strings like `x = """..."""`.
+                if open.starts_with(self.syntax.quotes) && !is_whole_line {
+                    match line[text_start..].find(end) {
+                        Some(length) => {
+                            skip_to = text_start + length + end.len()
+                        }
+                        None => {
+                            self.string_end = Some(end);
+                            return comments;
+                        }
+                    }
+                    continue;
+                }
+                match line[text_start..].find(end) {
+                    Some(length) => {
+                        comments.push(Comment {
+                            text: &line[text_start..text_start + length],
+                            start: text_start,
+                            is_block: true,
+                            is_whole_line,
+                        });
+                        skip_to = text_start + length + end.len();
+                    }
+                    None => {
+                        comments.push(Comment {
+                            text: &line[text_start..],
+                            start: text_start,
+                            is_block: true,
+                            is_whole_line,
+                        });
+                        self.block_end = Some(end);
+                        return comments;
+                    }
+                }
+                continue;
+            }
+            if let Some(marker) = self
+                .syntax
+                .line_comments
+                .iter()
+                .find(|marker| rest.starts_with(*marker))
+            {
+                comments.push(Comment {
+                    text: &line[index + marker.len()..],
+                    start: index + marker.len(),
+                    is_block: false,
+                    is_whole_line,
+                });
+                return comments;
+            }
+            if self.syntax.char_literals && c == '\'' {
+                if let Some(length) = get_char_literal_length(rest) {
+                    skip_to = index + length;
+                }
+                continue;
+            }
+            if self.syntax.quotes.contains(&c) {
+                quote = Some(c);
+            }
+        }
+
+        comments
+    }
+}
+
+fn get_char_literal_length(text: &str) -> Option<usize> {
+    //! Returns the length in bytes of the char literal the text starts
+    //! with, e.g. `'"'`, `'\''` or `'\u{1F600}'`, or None if the quote
+    //! starts a lifetime or label like `'a` instead.
+    let mut chars = text.strip_prefix('\'')?.chars();
+    match chars.next()? {
+        '\'' => None,
+        '\\' => {
+            let escaped = chars.next()?;
+            // The longest escape is a unicode one like `\u{10FFFF}`.
+            let end = chars.as_str().find('\'').filter(|end| *end <= 8)?;
+            Some(2 + escaped.len_utf8() + end + 1)
+        }
+        c => match chars.next()? {
+            '\'' => Some(1 + c.len_utf8() + 1),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(syntax: &'static Syntax, lines: &[&str]) -> Vec<String> {
+        let mut scanner = Scanner::new(syntax);
+        lines
+            .iter()
+            .flat_map(|line| scanner.scan(line))
+            .map(|comment| comment.text.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn finds_line_comments() {
+        assert_eq!(scan(&RUST, &["let x = 1; // TODO: y"]), [" TODO: y"]);
+        assert_eq!(scan(&HASH, &["# TODO: y"]), [" TODO: y"]);
+        assert!(scan(&HASH, &["let x = 1; // TODO: y"]).is_empty());
+    }
+
+    #[test]
+    fn skips_markers_in_strings() {
+        assert_eq!(scan(&C_LIKE, &[r#"s = "// not" // yes"#]), [" yes"]);
+        assert_eq!(scan(&C_LIKE, &[r#"s = "\"//" // yes"#]), [" yes"]);
+        assert_eq!(scan(&HASH, &["s = '#' # yes"]), [" yes"]);
+        assert!(scan(&GENERIC, &[r#"url = "http://x""#]).is_empty());
+    }
+
+    #[test]
+    fn skips_rust_char_literals() {
+        assert_eq!(scan(&RUST, &[r#"let c = '"'; // yes"#]), [" yes"]);
+        assert_eq!(scan(&RUST, &[r"let c = '\''; // yes"]), [" yes"]);
+        assert_eq!(scan(&RUST, &[r"let c = '\u{1F600}'; // yes"]), [" yes"]);
+    }
+
+    #[test]
+    fn ignores_rust_lifetimes() {
+        assert_eq!(
+            scan(&RUST, &["fn f<'a>(s: &'a str) -> &'a str { s } // yes"]),
+            [" yes"]
+        );
+        assert_eq!(scan(&RUST, &["'outer: loop {} // yes"]), [" yes"]);
+    }
+
+    #[test]
+    fn get_char_literal_length_measures_literals() {
+        assert_eq!(get_char_literal_length("'a'"), Some(3));
+        assert_eq!(get_char_literal_length("'é' x"), Some(4));
+        assert_eq!(get_char_literal_length(r"'\n'"), Some(4));
+        assert_eq!(get_char_literal_length(r"'\x7f'"), Some(6));
+        assert_eq!(get_char_literal_length("'a>"), None);
+        assert_eq!(get_char_literal_length("''"), None);
+    }
+
+    #[test]
+    fn finds_block_comments() {
+        assert_eq!(
+            scan(&C_LIKE, &["x = 1; /* a */ y = 2; // b"]),
+            [" a ", " b"]
+        );
+        assert_eq!(
+            scan(&C_LIKE, &["/* TODO: a", " * b", " */ x = 1; // c"]),
+            [" TODO: a", " * b", " ", " c"]
+        );
+        assert_eq!(
+            scan(&RUBY, &["=begin", "TODO: a", "=end"]),
+            ["", "TODO: a", ""]
+        );
+        assert!(scan(&RUBY, &["x = 1 =begin"]).is_empty());
+    }
+
+    #[test]
+    fn treats_whole_line_triple_quotes_as_docstrings() {
+        assert_eq!(
+            scan(&PYTHON, &[r#"    """TODO: a"#, r#"    b""" # c"#]),
+            ["TODO: a", "    b", " c"]
+        );
+        assert_eq!(scan(&PYTHON, &["'''TODO: a'''"]), ["TODO: a"]);
+    }
+
+    #[test]
+    fn skips_triple_quoted_strings() {
+        assert_eq!(scan(&PYTHON, &[r#"x = """a # b""" # c"#]), [" c"]);
+        assert_eq!(scan(&PYTHON, &["x = '''", "# TODO: a", "''' # b"]), [" b"]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str;
+
+use super::cli;
+use super::command;
+use super::tracker;
+
+const GH_HOSTS_FILE: &str = "hosts.yml";
+const GH_TOKEN_KEY: &str = "oauth_token";
+
+pub fn find_token(
+    token: Option<&str>,
+    tracker: &str,
+    host: &str,
+    is_verbose: bool,
+) -> Option<String> {
+    //! Looks up the access token for the tracker, trying in order:
+    //! 1. the token given on the command line
+    //! 2. the tracker's environment variables, e.g. GITHUB_TOKEN
+    //! 3. the config of the GitHub CLI, for GitHub remotes
+    //! 4. `git credential fill` for the remote's host
+    //! 5. prompting the user, if there's a terminal to prompt in
+    //!
+    //! Returns None and outputs an error if no token was found.
+    let found = token
+        .map(|token| (token.to_string(), "the command line".to_string()))
+        .or_else(|| find_in_env(tracker))
+        .or_else(|| match tracker == tracker::GITHUB {
+            true => find_in_gh_config(host),
+            false => None,
+        })
+        .or_else(|| find_in_git_credentials(host));
+
+    let (token, source) = match found {
+        Some(found) => found,
+        None if cli::is_interactive() => {
+            (command::read_access_token(), "the prompt".to_string())
+        }
+        None => {
+            cli::print_error(&format!(
+                "No access token found. Pass one with --token or set {}.",
+                get_env_vars(tracker).join(" or ")
+            ));
+            return None;
+        }
+    };
+
+    if is_verbose {
+        cli::print_dim(&format!("Using the access token from {}.", source));
+    }
+    Some(token)
+}
+
+fn get_env_vars(tracker: &str) -> &'static [&'static str] {
+    match tracker {
+        tracker::GITLAB => &["GITLAB_TOKEN"],
+        tracker::GITEA => &["GITEA_TOKEN"],
+        tracker::JIRA => &["JIRA_API_TOKEN"],
+        _ => &["GITHUB_TOKEN", "GH_TOKEN"],
+    }
+}
+
+fn find_in_env(tracker: &str) -> Option<(String, String)> {
+    get_env_vars(tracker).iter().find_map(|name| {
+        let value = env::var(name).ok()?;
+        match value.trim().is_empty() {
+            true => None,
+            false => Some((value.trim().to_string(), format!("${}", name))),
+        }
+    })
+}
+
+fn find_in_gh_config(host: &str) -> Option<(String, String)> {
+    //! Reads the token stored by `gh auth login` for the host from the
+    //! GitHub CLI's hosts.yml, see parse_gh_hosts.
+    //!
+    //! Tokens kept in the system keyring instead aren't found.
+    let path = get_gh_config_dir()?.join(GH_HOSTS_FILE);
+    let contents = fs::read_to_string(&path).ok()?;
+    let token = parse_gh_hosts(&contents, host)?;
+
+    Some((token, path.to_string_lossy().to_string()))
+}
+
+fn parse_gh_hosts(contents: &str, host: &str) -> Option<String> {
+    //! Returns the token of the host's active user from the contents of a
+    //! hosts.yml, which looks like:
+    //!
+    //! github.com:
+    //!     users:
+    //!         octocat:
+    //!             oauth_token: gho_xxx
+    //!     user: octocat
+    //!     oauth_token: gho_xxx
+    //!
+    //! Versions of gh before multiple accounts only have the `user` and
+    //! `oauth_token` of the host. Newer ones may keep the token under
+    //! `users` alone, which is used if the host has none of its own.
+    let mut is_in_host = false;
+    let mut is_in_users = false;
+    // The indentation of the host's keys, and of the users' names.
+    let mut host_indent = None;
+    let mut user_indent = None;
+    let mut active_user = None;
+    let mut user = None;
+    let mut user_tokens = Vec::new();
+
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match trimmed.split_once(':') {
+            Some((key, value)) => (unquote(key), unquote(value)),
+            None => continue,
+        };
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 {
+            is_in_host = key == host;
+            continue;
+        }
+        if !is_in_host {
+            continue;
+        }
+
+        if indent <= *host_indent.get_or_insert(indent) {
+            is_in_users = key == "users";
+            match key {
+                GH_TOKEN_KEY if !value.is_empty() => {
+                    return Some(value.to_string())
+                }
+                "user" => active_user = Some(value),
+                _ => {}
+            }
+        } else if is_in_users {
+            if indent <= *user_indent.get_or_insert(indent) {
+                user = Some(key);
+            } else if key == GH_TOKEN_KEY && !value.is_empty() {
+                user_tokens.push((user, value));
+            }
+        }
+    }
+
+    user_tokens
+        .iter()
+        .find(|(user, _)| active_user.is_some() && *user == active_user)
+        .or_else(|| user_tokens.first())
+        .map(|(_, token)| token.to_string())
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+fn get_gh_config_dir() -> Option<PathBuf> {
+    //! Returns the GitHub CLI's config directory, which follows
+    //! $GH_CONFIG_DIR, then $XDG_CONFIG_HOME and defaults to ~/.config/gh.
+    if let Ok(dir) = env::var("GH_CONFIG_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        return Some(PathBuf::from(dir).join("gh"));
+    }
+    let home = env::var("HOME").or_else(|_| env::var("USERPROFILE")).ok()?;
+
+    Some(PathBuf::from(home).join(".config").join("gh"))
+}
+
+fn find_in_git_credentials(host: &str) -> Option<(String, String)> {
+    //! Executes the command `git credential fill` for https://{host}.
+    //! Returns the password of the stored credential, which is the token
+    //! for the hosts supported. Credential helpers aren't allowed to
+    //! prompt, since the user is prompted last anyway.
+    if host.is_empty() {
+        return None;
+    }
+
+    let mut child = Command::new("git")
+        .arg("credential")
+        .arg("fill")
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GCM_INTERACTIVE", "never")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()?
+        .write_all(format!("protocol=https\nhost={}\n\n", host).as_bytes())
+        .ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let password = str::from_utf8(&output.stdout)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("password="))?
+        .to_string();
+    match password.is_empty() {
+        true => None,
+        false => Some((password, "git credential fill".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_legacy_hosts() {
+        let contents = "github.com:\n    user: octocat\n    \
+                        oauth_token: gho_legacy\n    git_protocol: https\n";
+
+        assert_eq!(
+            parse_gh_hosts(contents, "github.com").as_deref(),
+            Some("gho_legacy")
+        );
+        assert_eq!(parse_gh_hosts(contents, "ghe.corp"), None);
+    }
+
+    #[test]
+    fn parses_tokens_nested_under_users() {
+        let contents = "\
+github.com:
+    users:
+        hubot:
+            oauth_token: gho_hubot
+        octocat:
+            oauth_token: gho_octocat
+    git_protocol: https
+    user: octocat
+";
+
+        assert_eq!(
+            parse_gh_hosts(contents, "github.com").as_deref(),
+            Some("gho_octocat")
+        );
+        let without_user = contents.replace("    user: octocat\n", "");
+        assert_eq!(
+            parse_gh_hosts(&without_user, "github.com").as_deref(),
+            Some("gho_hubot")
+        );
+    }
+
+    #[test]
+    fn prefers_the_token_of_the_host() {
+        let contents = "\
+github.com:
+  users:
+    octocat:
+      oauth_token: gho_nested
+  user: octocat
+  oauth_token: gho_host
+";
+
+        assert_eq!(
+            parse_gh_hosts(contents, "github.com").as_deref(),
+            Some("gho_host")
+        );
+    }
+
+    #[test]
+    fn skips_other_hosts() {
+        let contents = "\
+ghe.corp:
+    user: octocat
+    oauth_token: ghe_token
+github.com:
+    user: octocat
+    oauth_token: gho_token
+";
+
+        assert_eq!(
+            parse_gh_hosts(contents, "github.com").as_deref(),
+            Some("gho_token")
+        );
+        assert_eq!(
+            parse_gh_hosts(contents, "ghe.corp").as_deref(),
+            Some("ghe_token")
+        );
+    }
+
+    #[test]
+    fn unquotes_values() {
+        let contents = "\"github.com\":\n    \
+                        oauth_token: \"gho_quoted\"\n";
+
+        assert_eq!(
+            parse_gh_hosts(contents, "github.com").as_deref(),
+            Some("gho_quoted")
+        );
+        assert_eq!(
+            parse_gh_hosts("github.com:\n    oauth_token: ''\n", "github.com"),
+            None
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use console::style;
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::value::Value;
+
+use super::cli;
+use super::command;
+use super::config;
+use super::gitea;
+use super::gitlab;
+use super::issue;
+use super::jira;
+use super::local;
+use super::request;
+
+use command::Remote;
+use config::Config;
+use gitea::Gitea;
+use gitlab::GitLab;
+use issue::{Issue, Response};
+use jira::Jira;
+use local::Local;
+use request::Request;
+
+pub const GITHUB: &str = "github";
+pub const GITLAB: &str = "gitlab";
+pub const GITEA: &str = "gitea";
+pub const JIRA: &str = "jira";
+pub const LOCAL: &str = "local";
+pub const TRACKERS: &[&str] = &[GITHUB, GITLAB, GITEA, JIRA, LOCAL];
+
+// Parts of a host name that identify a tracker other than GitHub.
+const HOST_PARTS: &[(&str, &str)] = &[
+    ("gitlab", GITLAB),
+    ("gitea", GITEA),
+    ("forgejo", GITEA),
+    ("codeberg", GITEA),
+];
+pub const DEFAULT_PER_PAGE: usize = 100;
+pub const MAX_PER_PAGE: usize = 100;
+
+pub trait IssueTracker {
+    /// Returns the web url of the remote repository.
+    fn get_remote_url(&self) -> &str;
+
+    /// Returns every issue (open and closed) with the label.
+    fn get_issues_with_label(&self, label: &str) -> Option<Vec<Response>>;
+
+    /// Creates an issue and returns its number.
+    fn open_issue(&self, issue: &Issue) -> Option<usize>;
+
+    /// Replaces the body of an issue.
+    fn update_issue(&self, number: usize, body: &str) -> bool;
+
+    /// Comments on an issue and closes it.
+    fn close_issue(&self, number: usize, comment: &str) -> bool;
+
+    fn get_blob_url(&self, _commit: &str) -> Option<String> {
+        //! Returns the web url of the files in a commit, which code is
+        //! linked to from issue bodies, or None if there's no repository.
+        None
+    }
+
+    fn format_number(&self, number: usize) -> String {
+        //! Returns how the tracker refers to an issue, e.g. #42.
+        format!("#{}", number)
+    }
+
+    fn get_issues(
+        &self,
+        labels: &[String],
+        is_dry_run: bool,
+    ) -> Option<Vec<Response>> {
+        //! Retrieves all issues (open and closed) with any of the keyword
+        //! labels in the remote repository.
+        //!
+        //! Returns a vector of the issues, used to match them with todos by
+        //! their fingerprint. Returns early if any request fails.
+        if is_dry_run {
+            return Some(Vec::new());
+        }
+
+        let mut issues: Vec<Response> = Vec::new();
+        for label in labels {
+            cli::print_info(&format!(
+                "Fetching all issues with {} label from {}",
+                style(label).cyan(),
+                style(self.get_remote_url()).italic()
+            ));
+            for result in self.get_issues_with_label(label)? {
+                // Issues with several keyword labels are listed once.
+                if !issues.iter().any(|i| i.get_number() == result.get_number())
+                {
+                    issues.push(result);
+                }
+            }
+        }
+
+        match issues.len() {
+            0 => cli::print_info(
+                "No previously opened issues found in the remote repo.",
+            ),
+            n => cli::print_info(&format!(
+                "Found {} previously opened {} in the remote repo.",
+                style(n).bold(),
+                cli::handle_plural(&n, "issue")
+            )),
+        };
+        Some(issues)
+    }
+}
+
+pub fn uses_remote(tracker: Option<&str>) -> bool {
+    //! Returns whether the tracker's issues live with the git remote,
+    //! unlike Jira projects and the local store.
+    !matches!(tracker, Some(JIRA) | Some(LOCAL))
+}
+
+pub fn uses_token(tracker: &str) -> bool {
+    //! Returns whether the tracker needs an access token.
+    tracker != LOCAL
+}
+
+pub fn detect(host: &str) -> &'static str {
+    //! Guesses the tracker of a remote from its host, e.g. gitlab.com,
+    //! gitlab.example.com or gitea.example.com. Defaults to GitHub.
+    HOST_PARTS
+        .iter()
+        .find(|(part, _)| host.split('.').any(|p| p == *part))
+        .map(|(_, tracker)| *tracker)
+        .unwrap_or(GITHUB)
+}
+
+pub fn new_tracker(
+    tracker: &str,
+    token: String,
+    remote: &Remote,
+    config: &Config,
+    per_page: usize,
+) -> Option<Box<dyn IssueTracker>> {
+    //! Creates the backend for the tracker, which must be one of TRACKERS.
+    //!
+    //! Returns None and outputs an error if the tracker's settings are
+    //! missing.
+    let api_url = config.get_api_url();
+    let tracker: Box<dyn IssueTracker> = match tracker {
+        GITLAB => Box::new(GitLab::new(token, remote, api_url, per_page)?),
+        GITEA => Box::new(Gitea::new(token, remote, api_url, per_page)),
+        JIRA => {
+            let jira = match &config.jira {
+                Some(jira) => jira,
+                None => {
+                    cli::print_error(
+                        "The jira tracker needs a [jira] section with \
+                         the project key in the config file.",
+                    );
+                    return None;
+                }
+            };
+            let url = match api_url.or(jira.url.as_deref()) {
+                Some(url) => url,
+                None => {
+                    cli::print_error(
+                        "The jira tracker needs the url of the Jira site.",
+                    );
+                    return None;
+                }
+            };
+            Box::new(Jira::new(token, url, jira, per_page))
+        }
+        LOCAL => Box::new(Local::new(
+            &command::get_repo_root(),
+            config.get_local_dir(),
+        )),
+        _ => Box::new(Request::new(token, remote, api_url, per_page)),
+    };
+
+    Some(tracker)
+}
+
+#[derive(Deserialize)]
+pub struct Milestone {
+    pub id: usize,
+    // GitHub refers to milestones by their number within the repository.
+    pub number: Option<usize>,
+    pub title: String,
+}
+
+pub fn find_milestone(
+    url: &str,
+    params: &Value,
+    build: impl Fn(&str) -> RequestBuilder,
+    title: &str,
+) -> Option<Milestone> {
+    //! Fetches every page of milestones and returns the one with the title.
+    //!
+    //! Returns None and outputs an error if there's none.
+    let milestones: Vec<Milestone> = get_all_pages(url, params, build)?;
+    let milestone = milestones.into_iter().find(|m| m.title == title);
+    if milestone.is_none() {
+        cli::print_error(&format!("No milestone titled \"{}\" found.", title));
+    }
+
+    milestone
+}
+
+pub fn get_all_pages<T: DeserializeOwned>(
+    url: &str,
+    params: &Value,
+    build: impl Fn(&str) -> RequestBuilder,
+) -> Option<Vec<T>> {
+    //! Makes GET requests built by the closure, following the
+    //! `Link: rel="next"` header until every page has been fetched.
+    //!
+    //! Returns the items of every page, or None if any response is not
+    //! successful.
+    let mut items = Vec::new();
+    let mut next_url = Some(url.to_string());
+    let mut page = 1;
+
+    while let Some(url) = next_url {
+        let mut builder = build(&url);
+        // Subsequent page urls already include the query string.
+        if page == 1 {
+            builder = builder.query(params);
+        }
+        let mut response = send_successfully(builder, &url)?;
+
+        next_url = get_link(response.headers(), "next");
+        match get_last_page(response.headers()) {
+            Some(last) => {
+                cli::print_dim(&format!("Fetched page {} of {}", page, last))
+            }
+            None => cli::print_dim(&format!("Fetched page {}", page)),
+        }
+
+        if let Ok(json_array) = response.json::<Vec<T>>() {
+            items.extend(json_array);
+        }
+        page += 1;
+    }
+
+    Some(items)
+}
+
+fn get_link(headers: &HeaderMap, rel: &str) -> Option<String> {
+    //! Parses a `Link` header of the form
+    //! `<url>; rel="next", <url>; rel="last"` and returns the url
+    //! matching the given relation, if any.
+    let value = headers.get(LINK)?.to_str().ok()?;
+    let target = format!("rel=\"{}\"", rel);
+
+    value.split(',').find_map(|link| {
+        let mut parts = link.split(';');
+        let url = parts.next()?.trim();
+        if !parts.any(|part| part.trim() == target) {
+            return None;
+        }
+        url.strip_prefix('<')
+            .and_then(|url| url.strip_suffix('>'))
+            .map(|url| url.to_string())
+    })
+}
+
+fn get_last_page(headers: &HeaderMap) -> Option<usize> {
+    //! Returns the page number of the `rel="last"` link, used to report
+    //! progress while paging through issues.
+    let url = get_link(headers, "last")?;
+    let query = url.split('?').nth(1)?;
+
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+}
+
+pub fn send(builder: RequestBuilder, url: &str) -> Option<reqwest::Response> {
+    //! Sends the request built for the url.
+    //!
+    //! Returns None and outputs an error if no response was received, e.g.
+    //! because the host doesn't exist or can't be reached.
+    match builder.send() {
+        Ok(response) => Some(response),
+        Err(error) => {
+            // The error's own message starts with the url when it has one.
+            let message = error.to_string();
+            let reason = match error.url() {
+                Some(url) => message
+                    .strip_prefix(&format!("{}: ", url))
+                    .unwrap_or(&message),
+                None => &message,
+            };
+            cli::print_error(&format!(
+                "Failed to send a request to {}: {}",
+                url, reason
+            ));
+            None
+        }
+    }
+}
+
+pub fn send_successfully(
+    builder: RequestBuilder,
+    url: &str,
+) -> Option<reqwest::Response> {
+    //! Sends the request built for the url, see send.
+    //!
+    //! Returns None and outputs an error if the response is not successful.
+    let response = send(builder, url)?;
+
+    match is_successful_response(response.status()) {
+        true => Some(response),
+        false => None,
+    }
+}
+
+pub fn is_successful_response(status: StatusCode) -> bool {
+    //! Asserts that the status code returned is a success,
+    //! e.g. 200 OK or 201 Created.
+    //!
+    //! Otherwise, outputs a detailed description about the error.
+    if status.is_success() {
+        return true;
+    }
+
+    match status {
+        StatusCode::UNAUTHORIZED => cli::print_error(
+            "Unathorized request. \
+             Make sure your access token is valid and \
+             you have pull access to the repository.",
+        ),
+        StatusCode::GONE => {
+            cli::print_error("Issues are disabled in this repository.");
+        }
+        StatusCode::FORBIDDEN => cli::print_error(
+            "You have reached the API rate limit \
+             or your access token lacks permissions. \
+             Please try again later.",
+        ),
+        StatusCode::NOT_FOUND => cli::print_error(
+            "Remote repository not found. \
+             If your repository is private check that \
+             your access token has the correct permissions.",
+        ),
+        StatusCode::UNPROCESSABLE_ENTITY => {
+            cli::print_error("Unable to process request.");
+        }
+        s => cli::print_error(
+            &format!("Received unexpected status code {}", s).to_string(),
+        ),
+    };
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn link_headers(link: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LINK, HeaderValue::from_static(link));
+        headers
+    }
+
+    #[test]
+    fn get_link_finds_relation() {
+        let headers = link_headers(
+            "<https://api.github.com/issues?page=2>; rel=\"next\", \
+             <https://api.github.com/issues?page=5>; rel=\"last\"",
+        );
+
+        assert_eq!(
+            get_link(&headers, "next").as_deref(),
+            Some("https://api.github.com/issues?page=2")
+        );
+        assert_eq!(
+            get_link(&headers, "last").as_deref(),
+            Some("https://api.github.com/issues?page=5")
+        );
+        assert_eq!(get_link(&headers, "prev"), None);
+        assert_eq!(get_link(&HeaderMap::new(), "next"), None);
+    }
+
+    #[test]
+    fn get_last_page_reads_page_parameter() {
+        let headers = link_headers(
+            "<https://x/issues?page=2&per_page=100>; rel=\"next\", \
+             <https://x/issues?per_page=100&page=12>; rel=\"last\"",
+        );
+        assert_eq!(get_last_page(&headers), Some(12));
+
+        let headers = link_headers("<https://x/issues?page=1>; rel=\"prev\"");
+        assert_eq!(get_last_page(&headers), None);
+    }
+}